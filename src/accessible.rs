@@ -1,71 +1,189 @@
-use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
 
-pub type AccessFunction<T> = Box<dyn Fn(&T) -> Option<String>>;
+pub type AccessFunction<T> = fn(&T) -> Option<String>;
 
-pub struct Accessor<T> {
-    properties: HashMap<String, AccessFunction<T>>,
+// A table of named properties for a type. Tables are meant to live in a
+// `static` so that every instance of the type shares a single copy.
+pub struct Accessor<T: 'static> {
+    properties: &'static [(&'static str, AccessFunction<T>)],
 }
 
 impl<T> Accessor<T> {
-    pub fn new() -> Accessor<T> {
-        Accessor {
-            properties: HashMap::new(),
-        }
-    }
-
-    pub fn register(&mut self, key: &str, function: AccessFunction<T>) {
-        self.properties.insert(key.to_owned(), function);
+    pub const fn new(properties: &'static [(&'static str, AccessFunction<T>)]) -> Accessor<T> {
+        Accessor { properties }
     }
 
     pub fn lookup(&self, key: &str, accessible: &T) -> Option<String> {
-        let access_function = self.properties.get(key)?;
+        let (_, access_function) = self.properties.iter().find(|(name, _)| *name == key)?;
         access_function(accessible)
     }
+
+    pub fn property_names(&self) -> Vec<&'static str> {
+        self.properties.iter().map(|(name, _)| *name).collect()
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct AccessPath {
-    segments: Vec<String>,
+    path: String,
+    // Byte ranges of each segment within path.
+    segments: Vec<Range<usize>>,
 }
 
 impl AccessPath {
     pub fn from(string: String) -> AccessPath {
-        let mut path = AccessPath {
-            segments: Vec::new(),
-        };
-        for segment in string.split(".") {
-            path.segments.push(segment.to_owned());
+        let mut segments = Vec::new();
+        let mut start = 0;
+        for segment in string.split('.') {
+            segments.push(start..start + segment.len());
+            start += segment.len() + 1;
+        }
+        AccessPath {
+            path: string,
+            segments,
         }
-        path
     }
 
-    pub fn view(&self) -> AccessPathView {
+    #[allow(dead_code)]
+    pub fn as_str(&self) -> &str {
+        &self.path
+    }
+
+    pub fn view(&self) -> AccessPathView<'_> {
         AccessPathView {
+            path: &self.path,
             segments: &self.segments[..],
         }
     }
 }
 
+impl fmt::Display for AccessPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.path)
+    }
+}
+
+#[derive(Clone, Copy)]
 pub struct AccessPathView<'a> {
-    segments: &'a [String],
+    path: &'a str,
+    segments: &'a [Range<usize>],
+}
+
+impl<'a> AccessPathView<'a> {
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn segment(&self, index: usize) -> &'a str {
+        &self.path[self.segments[index].clone()]
+    }
+
+    // The view with its first segment removed.
+    pub fn rest(&self) -> AccessPathView<'a> {
+        AccessPathView {
+            path: self.path,
+            segments: &self.segments[1..],
+        }
+    }
 }
 
 pub trait Accessible {
     fn lookup(&self, path: AccessPathView) -> Option<String> {
-        if path.segments.len() == 0 {
-            return None;
-        }
-        if path.segments.len() == 1 {
-            return self.lookup_local(&path.segments[0]);
+        match path.len() {
+            0 => None,
+            1 => self.lookup_local(path.segment(0)),
+            _ => self.get_child(path.segment(0))?.lookup(path.rest()),
         }
-        self.get_child(&path.segments[0])?.lookup(AccessPathView {
-            segments: &path.segments[1..],
-        })
     }
     fn lookup_local(&self, property: &str) -> Option<String>;
-    fn get_child(&self, _child: &str) -> Option<&Accessible> {
+    fn get_child(&self, _child: &str) -> Option<&dyn Accessible> {
+        None
+    }
+    // Named collections, iterated over by template `{for}` blocks.
+    fn get_list(&self, _list: &str) -> Option<Vec<&dyn Accessible>> {
         None
     }
+    fn lookup_list(&self, path: AccessPathView) -> Option<Vec<&dyn Accessible>> {
+        match path.len() {
+            0 => None,
+            1 => self.get_list(path.segment(0)),
+            _ => self.get_child(path.segment(0))?.lookup_list(path.rest()),
+        }
+    }
+
+    // Stores value in a writable property, returning whether property
+    // accepted it.
+    fn set_local(&mut self, _property: &str, _value: &str) -> bool {
+        false
+    }
+    fn get_child_mut(&mut self, _child: &str) -> Option<&mut dyn Accessible> {
+        None
+    }
+    fn assign(&mut self, path: AccessPathView, value: &str) -> bool {
+        match path.len() {
+            0 => false,
+            1 => self.set_local(path.segment(0), value),
+            _ => match self.get_child_mut(path.segment(0)) {
+                Some(child) => child.assign(path.rest(), value),
+                None => false,
+            },
+        }
+    }
+
+    // Names accepted by lookup_local. A property may still look up to None,
+    // e.g. an optional value that is currently unset.
+    fn property_names(&self) -> Vec<&str>;
+    // Names accepted by get_child.
+    #[allow(dead_code)]
+    fn child_names(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    // Whether path names a property, regardless of its current value.
+    #[allow(dead_code)]
+    fn has_path(&self, path: AccessPathView) -> bool {
+        match path.len() {
+            0 => false,
+            1 => self.property_names().contains(&path.segment(0)),
+            _ => match self.get_child(path.segment(0)) {
+                Some(child) => child.has_path(path.rest()),
+                None => false,
+            },
+        }
+    }
+
+    // Every property path reachable from here, in dotted form.
+    #[allow(dead_code)]
+    fn property_paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self
+            .property_names()
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        for child_name in self.child_names() {
+            if let Some(child) = self.get_child(child_name) {
+                for path in child.property_paths() {
+                    paths.push(format!("{}.{}", child_name, path));
+                }
+            }
+        }
+        paths
+    }
+
+    // Property paths starting with prefix, for autocompletion.
+    #[allow(dead_code)]
+    fn complete(&self, prefix: &str) -> Vec<String> {
+        self.property_paths()
+            .into_iter()
+            .filter(|path| path.starts_with(prefix))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -76,14 +194,24 @@ mod tests {
         a: String,
         b: String,
         child: TestSubcontainer,
-        accessor: Accessor<TestContainer>,
     }
 
+    static TEST_CONTAINER_ACCESSOR: Accessor<TestContainer> = Accessor::new(&[
+        ("a", |container| Some(container.a.clone())),
+        ("b", |container| Some(container.b.clone())),
+    ]);
+
     impl Accessible for TestContainer {
         fn lookup_local(&self, property: &str) -> Option<String> {
-            self.accessor.lookup(property, self)
+            TEST_CONTAINER_ACCESSOR.lookup(property, self)
+        }
+        fn property_names(&self) -> Vec<&str> {
+            TEST_CONTAINER_ACCESSOR.property_names()
         }
-        fn get_child(&self, child: &str) -> Option<&Accessible> {
+        fn child_names(&self) -> Vec<&str> {
+            vec!["child"]
+        }
+        fn get_child(&self, child: &str) -> Option<&dyn Accessible> {
             match child {
                 "child" => Some(&self.child),
                 _ => None,
@@ -93,40 +221,31 @@ mod tests {
 
     struct TestSubcontainer {
         c: String,
-        accessor: Accessor<TestSubcontainer>,
     }
 
+    static TEST_SUBCONTAINER_ACCESSOR: Accessor<TestSubcontainer> =
+        Accessor::new(&[("c", |container| Some(container.c.clone()))]);
+
     impl Accessible for TestSubcontainer {
         fn lookup_local(&self, property: &str) -> Option<String> {
-            self.accessor.lookup(property, self)
+            TEST_SUBCONTAINER_ACCESSOR.lookup(property, self)
+        }
+        fn property_names(&self) -> Vec<&str> {
+            TEST_SUBCONTAINER_ACCESSOR.property_names()
         }
     }
 
     #[test]
     fn looks_up_properties() {
-        let mut container = TestContainer {
+        let container = TestContainer {
             a: String::from("foo"),
             b: String::from("bar"),
             child: TestSubcontainer {
                 c: String::from("baz"),
-                accessor: Accessor::new(),
             },
-            accessor: Accessor::new(),
         };
-        container.accessor.register(
-            "a",
-            Box::new(|container: &TestContainer| Some(container.a.clone())),
-        );
-        container.accessor.register(
-            "b",
-            Box::new(|container: &TestContainer| Some(container.b.clone())),
-        );
-        container.child.accessor.register(
-            "c",
-            Box::new(|container: &TestSubcontainer| Some(container.c.clone())),
-        );
         assert_eq!(
-            container.accessor.lookup("a", &container),
+            TEST_CONTAINER_ACCESSOR.lookup("a", &container),
             Some(String::from("foo"))
         );
         assert_eq!(
@@ -158,4 +277,34 @@ mod tests {
             None
         );
     }
+
+    #[test]
+    fn enumerates_properties() {
+        let container = TestContainer {
+            a: String::from("foo"),
+            b: String::from("bar"),
+            child: TestSubcontainer {
+                c: String::from("baz"),
+            },
+        };
+        assert_eq!(container.property_names(), vec!["a", "b"]);
+        assert_eq!(container.child_names(), vec!["child"]);
+        assert_eq!(container.property_paths(), vec!["a", "b", "child.c"]);
+        assert_eq!(container.complete("chi"), vec!["child.c"]);
+        assert!(container.has_path(AccessPath::from(String::from("child.c")).view()));
+        assert!(!container.has_path(AccessPath::from(String::from("child")).view()));
+        assert!(!container.has_path(AccessPath::from(String::from("child.d")).view()));
+    }
+
+    #[test]
+    fn splits_paths_into_segments() {
+        let path = AccessPath::from(String::from("player.stats.strength"));
+        let view = path.view();
+        assert_eq!(view.len(), 3);
+        assert_eq!(view.segment(0), "player");
+        assert_eq!(view.segment(1), "stats");
+        assert_eq!(view.segment(2), "strength");
+        assert_eq!(view.rest().segment(0), "stats");
+        assert_eq!(path.to_string(), "player.stats.strength");
+    }
 }
@@ -1,26 +1,62 @@
 use crate::accessible::{Accessible, Accessor};
+use crate::inventory::Inventory;
 use crate::stat::StatBlock;
 
+#[derive(Clone)]
 pub struct Character {
     pub name: String,
     pub stats: StatBlock,
-    accessor: Accessor<Character>,
+    pub inventory: Inventory,
 }
 
+static CHARACTER_ACCESSOR: Accessor<Character> =
+    Accessor::new(&[("name", |c| Some(c.name.clone()))]);
+
 impl Character {
     pub fn new(name: String, stats: StatBlock) -> Character {
-        let mut accessor = Accessor::new();
-        accessor.register("name", Box::new(|c: &Character| Some(c.name.clone())));
         Character {
             name,
             stats,
-            accessor,
+            inventory: Inventory::new(),
         }
     }
 }
 
 impl Accessible for Character {
     fn lookup_local(&self, property: &str) -> Option<String> {
-        self.accessor.lookup(property, self)
+        CHARACTER_ACCESSOR.lookup(property, self)
+    }
+    fn set_local(&mut self, property: &str, value: &str) -> bool {
+        match property {
+            "name" => self.name = value.to_string(),
+            _ => return false,
+        }
+        true
+    }
+    fn get_child(&self, child: &str) -> Option<&dyn Accessible> {
+        match child {
+            // Reads well in conditions: `{if player.has_item.rope}`. The
+            // inventory itself is a list.
+            "has_item" => Some(&self.inventory),
+            _ => None,
+        }
+    }
+    fn get_list(&self, list: &str) -> Option<Vec<&dyn Accessible>> {
+        match list {
+            "inventory" => Some(
+                self.inventory
+                    .stacks()
+                    .into_iter()
+                    .map(|stack| stack as &dyn Accessible)
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+    fn property_names(&self) -> Vec<&str> {
+        CHARACTER_ACCESSOR.property_names()
+    }
+    fn child_names(&self) -> Vec<&str> {
+        vec!["has_item"]
     }
 }
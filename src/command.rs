@@ -0,0 +1,150 @@
+use crate::world::World;
+
+// Reserved words the player can enter at any choice prompt instead of
+// choosing an option.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Stats,
+    Inventory,
+    Save,
+    Load,
+    Undo,
+    Rewind,
+    Help,
+    Quit,
+}
+
+impl Command {
+    pub const ALL: [Command; 8] = [
+        Command::Stats,
+        Command::Inventory,
+        Command::Save,
+        Command::Load,
+        Command::Undo,
+        Command::Rewind,
+        Command::Help,
+        Command::Quit,
+    ];
+
+    pub fn parse(input: &str) -> Option<Command> {
+        let input = input.trim().to_lowercase();
+        Command::ALL
+            .iter()
+            .copied()
+            .find(|command| command.name() == input)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::Stats => "stats",
+            Command::Inventory => "inventory",
+            Command::Save => "save",
+            Command::Load => "load",
+            Command::Undo => "undo",
+            Command::Rewind => "rewind",
+            Command::Help => "help",
+            Command::Quit => "quit",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Command::Stats => "Show your stats.",
+            Command::Inventory => "List what you are carrying.",
+            Command::Save => "Save your game.",
+            Command::Load => "Load your saved game.",
+            Command::Undo => "Take back your last choice.",
+            Command::Rewind => "Go back to an earlier choice.",
+            Command::Help => "List these commands.",
+            Command::Quit => "Stop playing.",
+        }
+    }
+}
+
+// What the runner does after a command: write the message, then either
+// prompt for the same choice again or stop the story.
+#[derive(Debug, PartialEq)]
+pub enum CommandResponse {
+    Continue(String),
+    Stop(String),
+}
+
+// Runners consult a CommandHook first and fall back to builtin_response
+// when it returns None.
+pub type CommandHook = Box<dyn Fn(Command, &World) -> Option<CommandResponse> + Send + Sync>;
+
+pub fn builtin_response(command: Command, world: &World) -> CommandResponse {
+    match command {
+        Command::Stats => CommandResponse::Continue(world.player.stats.print_table()),
+        Command::Inventory => {
+            let stacks = world.player.inventory.stacks();
+            if stacks.is_empty() {
+                return CommandResponse::Continue(String::from("You aren't carrying anything."));
+            }
+            let mut listing = String::from("You are carrying:");
+            for stack in stacks {
+                listing.push_str(&format!("\n  {} x{}", stack.item().name(), stack.count()));
+            }
+            CommandResponse::Continue(listing)
+        }
+        Command::Help => {
+            let mut help = String::from("Enter the number of a choice, or one of:");
+            for command in Command::ALL.iter() {
+                help.push_str(&format!(
+                    "\n  {:<10}{}",
+                    command.name(),
+                    command.description()
+                ));
+            }
+            CommandResponse::Continue(help)
+        }
+        Command::Quit => CommandResponse::Stop(String::from("[[Goodbye! Thanks for playing.]]")),
+        // Story runners answer these themselves.
+        Command::Save | Command::Load | Command::Undo | Command::Rewind => {
+            CommandResponse::Continue(format!(
+                "[[`{}` is not available in this game.]]",
+                command.name()
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::Item;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(Command::parse("stats"), Some(Command::Stats));
+        assert_eq!(Command::parse(" Quit\n"), Some(Command::Quit));
+        assert_eq!(Command::parse("1"), None);
+        assert_eq!(Command::parse("dance"), None);
+    }
+
+    #[test]
+    fn lists_inventory() {
+        let mut world = World::empty();
+        assert_eq!(
+            builtin_response(Command::Inventory, &world),
+            CommandResponse::Continue(String::from("You aren't carrying anything."))
+        );
+        world.player.inventory.add_item(Item::new(
+            String::from("note"),
+            String::from("Note"),
+            vec![],
+        ));
+        assert_eq!(
+            builtin_response(Command::Inventory, &world),
+            CommandResponse::Continue(String::from("You are carrying:\n  Note x1"))
+        );
+    }
+
+    #[test]
+    fn quit_stops() {
+        assert_eq!(
+            builtin_response(Command::Quit, &World::empty()),
+            CommandResponse::Stop(String::from("[[Goodbye! Thanks for playing.]]"))
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use std::collections::HashMap;
 
+use crate::accessible::{Accessible, Accessor};
+
+#[derive(Clone)]
 pub struct Inventory {
     // Key is Item.id.
     items: HashMap<String, ItemStack>,
@@ -19,6 +22,14 @@ impl Inventory {
             .count += 1;
     }
 
+    // Adds count of item at once, e.g. when loading a saved game.
+    pub fn add_items(&mut self, item: Item, count: i64) {
+        self.items
+            .entry(item.id.clone())
+            .or_insert(ItemStack { item, count: 0 })
+            .add_n(count);
+    }
+
     pub fn get_stack_by_id(&self, id: &str) -> Option<&ItemStack> {
         let stack_option = self.items.get(id);
         if let Some(stack) = stack_option {
@@ -29,6 +40,7 @@ impl Inventory {
         stack_option
     }
 
+    #[allow(dead_code)]
     pub fn get_mut_stack_by_id(&mut self, id: &str) -> Option<&mut ItemStack> {
         let stack_option = self.items.get_mut(id);
         if let Some(stack) = &stack_option {
@@ -39,20 +51,42 @@ impl Inventory {
         stack_option
     }
 
+    // Non-empty stacks, ordered by item id.
+    pub fn stacks(&self) -> Vec<&ItemStack> {
+        let mut stacks: Vec<&ItemStack> =
+            self.items.values().filter(|stack| !stack.empty()).collect();
+        stacks.sort_by(|a, b| a.item.id.cmp(&b.item.id));
+        stacks
+    }
+
+    #[allow(dead_code)]
     pub fn total_item_count(&self) -> i64 {
         let mut count = 0;
-        for (_id, stack) in &self.items {
+        for stack in self.items.values() {
             count += stack.count;
         }
         count
     }
 
+    #[allow(dead_code)]
     pub fn empty(&self) -> bool {
         self.total_item_count() == 0
     }
 }
 
-#[derive(Debug, PartialEq)]
+// Item ids are properties whose value is the held count, so `{if
+// player.has_item.rope}` is true while a rope is held.
+impl Accessible for Inventory {
+    fn lookup_local(&self, property: &str) -> Option<String> {
+        self.get_stack_by_id(property)
+            .map(|stack| stack.count.to_string())
+    }
+    fn property_names(&self) -> Vec<&str> {
+        self.stacks().iter().map(|stack| stack.item.id()).collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct ItemStack {
     item: Item,
     count: i64,
@@ -67,6 +101,7 @@ impl ItemStack {
         self.count
     }
 
+    #[allow(dead_code)]
     pub fn add(&mut self) {
         self.count += 1;
     }
@@ -79,12 +114,14 @@ impl ItemStack {
         self.count += n
     }
 
+    #[allow(dead_code)]
     pub fn remove(&mut self) {
         if !self.empty() {
             self.count -= 1;
         }
     }
 
+    #[allow(dead_code)]
     pub fn remove_n(&mut self, n: i64) {
         if n < 0 {
             return;
@@ -101,7 +138,22 @@ impl ItemStack {
     }
 }
 
-#[derive(Debug, PartialEq)]
+static ITEM_STACK_ACCESSOR: Accessor<ItemStack> = Accessor::new(&[
+    ("id", |stack| Some(stack.item.id.clone())),
+    ("name", |stack| Some(stack.item.name.clone())),
+    ("count", |stack| Some(stack.count.to_string())),
+]);
+
+impl Accessible for ItemStack {
+    fn lookup_local(&self, property: &str) -> Option<String> {
+        ITEM_STACK_ACCESSOR.lookup(property, self)
+    }
+    fn property_names(&self) -> Vec<&str> {
+        ITEM_STACK_ACCESSOR.property_names()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Item {
     id: String,
     name: String,
@@ -125,19 +177,20 @@ impl Item {
         &self.name
     }
 
+    #[allow(dead_code)]
     pub fn behaviors(&self) -> &[ItemBehavior] {
         &self.behaviors
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ItemBehavior {
     Key(KeyData),
     // TODO(andrewmclees): Implement as other modules become available.
     Equipment,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct KeyData {
     doors: Vec<String>,
 }
@@ -147,6 +200,7 @@ impl KeyData {
         KeyData { doors }
     }
 
+    #[allow(dead_code)]
     pub fn opens(&self, door: &str) -> bool {
         for openable_door in &self.doors {
             if door == openable_door {
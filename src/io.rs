@@ -1,34 +1,192 @@
+pub mod json_lines;
+pub mod markup;
+pub mod text_parser;
+pub mod transcript;
+pub mod wrap;
+
 use std::collections::VecDeque;
+use std::env;
+use std::fs::File;
 use std::io;
-use std::io::Write;
-use std::iter::FromIterator;
+use std::io::{IsTerminal, Write};
+use std::process;
 
 use crate::choice::Choice;
+use crate::command::Command;
+
+// Why choose returned without a choice.
+#[derive(Debug, PartialEq)]
+pub enum Interrupt {
+    // The player entered a meta-command; the caller handles it and then
+    // usually asks again.
+    Command(Command),
+    EndOfInput,
+}
 
 pub trait Interface {
     fn write(&mut self, message: &str);
-    fn choose<T: Choice>(&mut self, choices: Vec<T>) -> T;
+    fn choose<T: Choice>(&mut self, choices: Vec<T>) -> Result<T, Interrupt>;
+    // Asks for a line of free text. The answer is trimmed and never empty.
+    fn ask_text(&mut self, question: &str) -> Result<String, Interrupt>;
+    // Asks until the player enters a whole number from min to max.
+    fn ask_number(&mut self, question: &str, min: i64, max: i64) -> Result<i64, Interrupt> {
+        loop {
+            match self.ask_text(question)?.parse::<i64>() {
+                Ok(number) if number >= min && number <= max => return Ok(number),
+                _ => self.write(&format!("[[Enter a number from {} to {}.]]", min, max)),
+            }
+        }
+    }
+}
+
+pub struct StandardIoInterface {
+    // Text is reflowed to this many columns; None prints it as written.
+    width: Option<usize>,
+    // Output pauses at a "--more--" prompt after this many lines; None
+    // never pauses.
+    page_height: Option<usize>,
+    lines_on_page: usize,
+    // Whether markup renders as ANSI styles rather than plain text.
+    styled: bool,
+}
+
+impl StandardIoInterface {
+    // Fits and styles output for the terminal, or prints plain text as
+    // written when stdout is not a terminal. Output is only paged when the
+    // player is typing, so that piped choices are not taken as keypresses.
+    pub fn new() -> StandardIoInterface {
+        if !io::stdout().is_terminal() {
+            return StandardIoInterface::with_layout(None, None, false);
+        }
+        let (width, height) = terminal_size();
+        let page_height = Some(height).filter(|_| io::stdin().is_terminal());
+        StandardIoInterface::with_layout(Some(width), page_height, true)
+    }
+
+    pub fn with_layout(
+        width: Option<usize>,
+        page_height: Option<usize>,
+        styled: bool,
+    ) -> StandardIoInterface {
+        StandardIoInterface {
+            width,
+            page_height,
+            lines_on_page: 0,
+            styled,
+        }
+    }
+
+    fn render_markup(&self, text: &str) -> String {
+        if self.styled {
+            markup::render_ansi(text)
+        } else {
+            markup::render_plain(text)
+        }
+    }
+
+    pub fn set_width(&mut self, width: Option<usize>) {
+        self.width = width;
+    }
+
+    fn print_line(&mut self, line: &str) {
+        if let Some(page_height) = self.page_height {
+            // The last row is left for the prompt.
+            if self.lines_on_page + 1 >= page_height {
+                print!("--more--");
+                io::stdout().flush().expect("failed to flush stdio");
+                let mut ignored = String::new();
+                if let Ok(0) | Err(_) = io::stdin().read_line(&mut ignored) {
+                    // Nobody is left to page through the output.
+                    println!();
+                    self.page_height = None;
+                }
+                self.lines_on_page = 0;
+            }
+        }
+        println!("{}", line);
+        self.lines_on_page += 1;
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        self.lines_on_page = 0;
+        // Read errors are treated like the end of input: there is no way to
+        // keep playing either way.
+        match io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line),
+        }
+    }
+}
+
+impl Default for StandardIoInterface {
+    fn default() -> StandardIoInterface {
+        StandardIoInterface::new()
+    }
 }
 
-pub struct StandardIoInterface;
+// Columns and rows of the terminal, from $COLUMNS and $LINES when set, then
+// from `stty size`, falling back to 80x24.
+fn terminal_size() -> (usize, usize) {
+    let from_env = |name: &str| env::var(name).ok().and_then(|value| value.parse().ok());
+    if let (Some(width), Some(height)) = (from_env("COLUMNS"), from_env("LINES")) {
+        return (width, height);
+    }
+    let stty_size = File::open("/dev/tty").ok().and_then(|tty| {
+        let output = process::Command::new("stty")
+            .arg("size")
+            .stdin(tty)
+            .output()
+            .ok()?;
+        let size = String::from_utf8(output.stdout).ok()?;
+        let mut size = size.split_whitespace().map(|n| n.parse::<usize>());
+        match (size.next(), size.next()) {
+            (Some(Ok(height)), Some(Ok(width))) if width > 0 && height > 0 => Some((width, height)),
+            _ => None,
+        }
+    });
+    stty_size.unwrap_or((80, 24))
+}
 
 impl Interface for StandardIoInterface {
     fn write(&mut self, message: &str) {
-        println!("{}", message);
+        let message = self.render_markup(message);
+        let message = match self.width {
+            Some(width) => wrap::reflow(&message, width),
+            None => message,
+        };
+        for line in message.split('\n') {
+            self.print_line(line);
+        }
     }
 
-    fn choose<T: Choice>(&mut self, mut choices: Vec<T>) -> T {
+    fn choose<T: Choice>(&mut self, mut choices: Vec<T>) -> Result<T, Interrupt> {
         for (i, choice) in choices.iter().enumerate() {
-            println!("{}) {}", i + 1, choice.describe());
+            let label = format!("{}) ", i + 1);
+            let text = format!("{}{}", label, self.render_markup(&choice.describe()));
+            match self.width {
+                Some(width) => {
+                    for line in wrap::wrap(&text, width, label.len()) {
+                        self.print_line(&line);
+                    }
+                }
+                None => self.print_line(&text),
+            }
         }
-        println!("What do you do?");
+        self.print_line("What do you do?");
         loop {
             print!("> ");
             io::stdout().flush().expect("failed to flush stdio");
-            let mut chosen = String::new();
-            io::stdin()
-                .read_line(&mut chosen)
-                .expect("failed to read from stdin");
+            let chosen = match self.read_line() {
+                Some(chosen) => chosen,
+                None => {
+                    println!();
+                    return Err(Interrupt::EndOfInput);
+                }
+            };
+            if let Some(command) = Command::parse(&chosen) {
+                return Err(Interrupt::Command(command));
+            }
             match chosen.trim().parse::<usize>() {
                 Ok(chosen_index) => {
                     if chosen_index < 1 || chosen_index > choices.len() {
@@ -38,37 +196,98 @@ impl Interface for StandardIoInterface {
                         );
                         continue;
                     }
-                    return choices.swap_remove(chosen_index - 1);
+                    return Ok(choices.swap_remove(chosen_index - 1));
                 }
                 Err(_) => {
-                    println!("Not a valid choice; enter a number.");
+                    println!("Not a valid choice; enter a number, or `help` for commands.");
                 }
             }
         }
     }
+
+    fn ask_text(&mut self, question: &str) -> Result<String, Interrupt> {
+        self.write(question);
+        loop {
+            print!("> ");
+            io::stdout().flush().expect("failed to flush stdio");
+            let answer = match self.read_line() {
+                Some(answer) => answer,
+                None => {
+                    println!();
+                    return Err(Interrupt::EndOfInput);
+                }
+            };
+            if let Some(command) = Command::parse(&answer) {
+                return Err(Interrupt::Command(command));
+            }
+            if !answer.trim().is_empty() {
+                return Ok(answer.trim().to_string());
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TestInput {
+    Choice(usize),
+    // An answer to ask_text or ask_number.
+    Text(String),
+    #[allow(dead_code)]
+    Command(Command),
 }
 
+#[allow(dead_code)]
 pub struct TestInterface {
     pub written: String,
-    pub preset_choices: VecDeque<usize>,
+    pub preset_inputs: VecDeque<TestInput>,
 }
 
+#[allow(dead_code)]
 impl TestInterface {
     pub fn new(preset_choices: VecDeque<usize>) -> TestInterface {
+        TestInterface::with_inputs(preset_choices.into_iter().map(TestInput::Choice).collect())
+    }
+
+    pub fn with_inputs(preset_inputs: VecDeque<TestInput>) -> TestInterface {
         TestInterface {
             written: String::new(),
-            preset_choices,
+            preset_inputs,
         }
     }
 }
 
 impl Interface for TestInterface {
+    // Markup is written as plain text.
     fn write(&mut self, message: &str) {
-        self.written.push_str(message);
+        self.written.push_str(&markup::render_plain(message));
     }
 
-    fn choose<T: Choice>(&mut self, mut choices: Vec<T>) -> T {
-        choices.swap_remove(self.preset_choices.pop_front().unwrap())
+    // Running out of preset inputs, or an input that is not one of the
+    // choices, ends the input.
+    fn choose<T: Choice>(&mut self, mut choices: Vec<T>) -> Result<T, Interrupt> {
+        match self.preset_inputs.pop_front() {
+            Some(TestInput::Choice(index)) if index < choices.len() => {
+                Ok(choices.swap_remove(index))
+            }
+            Some(TestInput::Command(command)) => Err(Interrupt::Command(command)),
+            _ => Err(Interrupt::EndOfInput),
+        }
+    }
+
+    // The question is written like any other message. Blank answers are
+    // skipped, and an input that is not text ends the input.
+    fn ask_text(&mut self, question: &str) -> Result<String, Interrupt> {
+        self.write(question);
+        loop {
+            match self.preset_inputs.pop_front() {
+                Some(TestInput::Text(text)) if !text.trim().is_empty() => {
+                    return Ok(text.trim().to_string())
+                }
+                Some(TestInput::Text(_)) => {}
+                Some(TestInput::Command(command)) => return Err(Interrupt::Command(command)),
+                _ => return Err(Interrupt::EndOfInput),
+            }
+        }
     }
 }
 
@@ -84,6 +303,8 @@ mod tests {
         assert_eq!(interface.written, "foo");
         interface.write(" bar");
         assert_eq!(interface.written, "foo bar");
+        interface.write(" *baz*");
+        assert_eq!(interface.written, "foo bar baz");
     }
 
     #[derive(Clone)]
@@ -105,9 +326,45 @@ mod tests {
             TestChoice { val: 42 },
             TestChoice { val: 123 },
         ];
-        assert_eq!(interface.choose(options.clone()).val, 12);
-        assert_eq!(interface.choose(options.clone()).val, 42);
-        assert_eq!(interface.choose(options.clone()).val, 12);
-        assert_eq!(interface.choose(options.clone()).val, 123);
+        assert_eq!(interface.choose(options.clone()).unwrap().val, 12);
+        assert_eq!(interface.choose(options.clone()).unwrap().val, 42);
+        assert_eq!(interface.choose(options.clone()).unwrap().val, 12);
+        assert_eq!(interface.choose(options.clone()).unwrap().val, 123);
+        assert_eq!(interface.choose(options).err(), Some(Interrupt::EndOfInput));
+    }
+
+    #[test]
+    pub fn test_interface_answers_questions() {
+        let mut interface = TestInterface::with_inputs(VecDeque::from(vec![
+            TestInput::Text(String::from("Ada")),
+            TestInput::Text(String::from("12")),
+            TestInput::Text(String::from("3")),
+        ]));
+        assert_eq!(interface.ask_text("Name?"), Ok(String::from("Ada")));
+        assert_eq!(interface.ask_number("How many?", 1, 5), Ok(3));
+        assert_eq!(
+            interface.written,
+            "Name?How many?Enter a number from 1 to 5.How many?"
+        );
+        assert_eq!(interface.ask_text("Name?"), Err(Interrupt::EndOfInput));
+    }
+
+    #[test]
+    pub fn test_interface_ends_input_on_the_wrong_kind_of_input() {
+        let mut interface = TestInterface::with_inputs(VecDeque::from(vec![
+            TestInput::Text(String::from("Ada")),
+            TestInput::Choice(3),
+            TestInput::Choice(0),
+            TestInput::Text(String::from("  ")),
+            TestInput::Text(String::from(" Ada ")),
+        ]));
+        let options = vec![TestChoice { val: 12 }];
+        assert_eq!(
+            interface.choose(options.clone()).err(),
+            Some(Interrupt::EndOfInput)
+        );
+        assert_eq!(interface.choose(options).err(), Some(Interrupt::EndOfInput));
+        assert_eq!(interface.ask_text("Name?"), Err(Interrupt::EndOfInput));
+        assert_eq!(interface.ask_text("Name?"), Ok(String::from("Ada")));
     }
 }
@@ -0,0 +1,264 @@
+use std::io::{self, BufRead, BufReader, Stdin, Stdout, Write};
+
+use crate::choice::Choice;
+use crate::command::Command;
+use crate::io::{markup, Interface, Interrupt};
+use crate::json::Json;
+
+// Drives the game from another process with one JSON object per line.
+//
+// Output events:
+//   {"type":"write","text":"...","markup":"..."}
+//   {"type":"prompt","options":[{"id":0,"text":"...","markup":"..."}]}
+//   {"type":"ask","text":"...","markup":"..."}
+//   {"type":"ask","text":"...","markup":"...","min":1,"max":10}
+//   {"type":"error","message":"..."}
+// Input, one per prompt or question:
+//   {"choice":0}
+//   {"text":"..."}
+//   {"command":"inventory"}
+//
+// "text" is plain; "markup" keeps the inline markup for frontends that style
+// it themselves.
+pub struct JsonLinesInterface<R, W> {
+    input: R,
+    output: W,
+}
+
+impl JsonLinesInterface<BufReader<Stdin>, Stdout> {
+    pub fn stdio() -> JsonLinesInterface<BufReader<Stdin>, Stdout> {
+        JsonLinesInterface::new(BufReader::new(io::stdin()), io::stdout())
+    }
+}
+
+impl<R: BufRead, W: Write> JsonLinesInterface<R, W> {
+    pub fn new(input: R, output: W) -> JsonLinesInterface<R, W> {
+        JsonLinesInterface { input, output }
+    }
+
+    #[allow(dead_code)]
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+
+    fn send(&mut self, event: Json) {
+        writeln!(self.output, "{}", event).expect("failed to write output");
+        self.output.flush().expect("failed to flush output");
+    }
+
+    fn send_error(&mut self, message: &str) {
+        self.send(Json::object(vec![
+            ("type", Json::string("error")),
+            ("message", Json::string(message)),
+        ]));
+    }
+
+    fn read_answer(&mut self) -> Result<Answer, Interrupt> {
+        loop {
+            let mut line = String::new();
+            if let Ok(0) | Err(_) = self.input.read_line(&mut line) {
+                return Err(Interrupt::EndOfInput);
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_answer(&line) {
+                Ok(Answer::Command(command)) => return Err(Interrupt::Command(command)),
+                Ok(answer) => return Ok(answer),
+                Err(message) => self.send_error(&message),
+            }
+        }
+    }
+
+    // Numbers are checked against bounds before they are returned.
+    fn ask(&mut self, question: &str, bounds: Option<(i64, i64)>) -> Result<String, Interrupt> {
+        let mut event = vec![
+            ("type", Json::string("ask")),
+            ("text", Json::String(markup::render_plain(question))),
+            ("markup", Json::string(question)),
+        ];
+        if let Some((min, max)) = bounds {
+            event.push(("min", Json::Number(min as f64)));
+            event.push(("max", Json::Number(max as f64)));
+        }
+        self.send(Json::object(event));
+        loop {
+            let text = match self.read_answer()? {
+                Answer::Text(text) if !text.is_empty() => text,
+                Answer::Text(_) => {
+                    self.send_error("`text` must not be empty");
+                    continue;
+                }
+                _ => {
+                    self.send_error("expected `text`");
+                    continue;
+                }
+            };
+            match bounds {
+                Some((min, max)) => match text.parse::<i64>() {
+                    Ok(number) if number >= min && number <= max => return Ok(text),
+                    _ => self.send_error(&format!("enter a number from {} to {}", min, max)),
+                },
+                None => return Ok(text),
+            }
+        }
+    }
+}
+
+enum Answer {
+    Choice(usize),
+    Text(String),
+    Command(Command),
+}
+
+fn parse_answer(line: &str) -> Result<Answer, String> {
+    let answer = Json::parse(line).map_err(|error| format!("invalid JSON: {}", error))?;
+    if let Some(choice) = answer.get("choice") {
+        return choice
+            .as_usize()
+            .map(Answer::Choice)
+            .ok_or_else(|| String::from("`choice` must be an option id"));
+    }
+    if let Some(text) = answer.get("text") {
+        return text
+            .as_str()
+            .map(|text| Answer::Text(text.trim().to_string()))
+            .ok_or_else(|| String::from("`text` must be a string"));
+    }
+    if let Some(command) = answer.get("command") {
+        let name = command.as_str().unwrap_or("");
+        return Command::parse(name)
+            .map(Answer::Command)
+            .ok_or_else(|| format!("unknown command `{}`", name));
+    }
+    Err(String::from("expected `choice`, `text` or `command`"))
+}
+
+impl<R: BufRead, W: Write> Interface for JsonLinesInterface<R, W> {
+    fn write(&mut self, message: &str) {
+        self.send(Json::object(vec![
+            ("type", Json::string("write")),
+            ("text", Json::String(markup::render_plain(message))),
+            ("markup", Json::string(message)),
+        ]));
+    }
+
+    fn choose<T: Choice>(&mut self, mut choices: Vec<T>) -> Result<T, Interrupt> {
+        let options = choices
+            .iter()
+            .enumerate()
+            .map(|(id, choice)| {
+                let description = choice.describe();
+                Json::object(vec![
+                    ("id", Json::Number(id as f64)),
+                    ("text", Json::String(markup::render_plain(&description))),
+                    ("markup", Json::String(description)),
+                ])
+            })
+            .collect();
+        self.send(Json::object(vec![
+            ("type", Json::string("prompt")),
+            ("options", Json::Array(options)),
+        ]));
+        loop {
+            match self.read_answer()? {
+                Answer::Choice(id) if id < choices.len() => return Ok(choices.swap_remove(id)),
+                Answer::Choice(id) => self.send_error(&format!("no option with id {}", id)),
+                _ => self.send_error("expected `choice`"),
+            }
+        }
+    }
+
+    fn ask_text(&mut self, question: &str) -> Result<String, Interrupt> {
+        self.ask(question, None)
+    }
+
+    fn ask_number(&mut self, question: &str, min: i64, max: i64) -> Result<i64, Interrupt> {
+        let answer = self.ask(question, Some((min, max)))?;
+        Ok(answer.parse().expect("ask checks numbers"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct TestChoice(&'static str);
+
+    impl Choice for TestChoice {
+        fn describe(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    fn written(interface: &JsonLinesInterface<&[u8], Vec<u8>>) -> String {
+        String::from_utf8(interface.output().clone()).unwrap()
+    }
+
+    #[test]
+    fn writes_events() {
+        let mut interface = JsonLinesInterface::new(&b""[..], Vec::new());
+        interface.write("@Stan: Hello.");
+        assert_eq!(
+            written(&interface),
+            "{\"type\":\"write\",\"text\":\"Stan: Hello.\",\"markup\":\"@Stan: Hello.\"}\n"
+        );
+    }
+
+    #[test]
+    fn prompts_and_reads_choices() {
+        let mut interface = JsonLinesInterface::new(
+            &b"not json\n{\"choice\":5}\n{\"choice\":1}\n"[..],
+            Vec::new(),
+        );
+        assert_eq!(
+            interface.choose(vec![TestChoice("Foo"), TestChoice("*Bar*")]),
+            Ok(TestChoice("*Bar*"))
+        );
+        let lines: Vec<String> = written(&interface).lines().map(String::from).collect();
+        assert_eq!(
+            lines[0],
+            "{\"type\":\"prompt\",\"options\":[\
+             {\"id\":0,\"text\":\"Foo\",\"markup\":\"Foo\"},\
+             {\"id\":1,\"text\":\"Bar\",\"markup\":\"*Bar*\"}]}"
+        );
+        assert!(lines[1].starts_with("{\"type\":\"error\",\"message\":\"invalid JSON"));
+        assert_eq!(
+            lines[2],
+            "{\"type\":\"error\",\"message\":\"no option with id 5\"}"
+        );
+    }
+
+    #[test]
+    fn reads_commands_and_end_of_input() {
+        let mut interface = JsonLinesInterface::new(&b"{\"command\":\"stats\"}\n"[..], Vec::new());
+        assert_eq!(
+            interface.choose(vec![TestChoice("Foo")]),
+            Err(Interrupt::Command(Command::Stats))
+        );
+        assert_eq!(
+            interface.choose(vec![TestChoice("Foo")]),
+            Err(Interrupt::EndOfInput)
+        );
+    }
+
+    #[test]
+    fn asks_questions() {
+        let mut interface = JsonLinesInterface::new(
+            &b"{\"choice\":0}\n{\"text\":\"11\"}\n{\"text\":\" 7 \"}\n"[..],
+            Vec::new(),
+        );
+        assert_eq!(interface.ask_number("*How* many?", 1, 10), Ok(7));
+        let lines: Vec<String> = written(&interface).lines().map(String::from).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "{\"type\":\"ask\",\"text\":\"How many?\",\"markup\":\"*How* many?\",\
+                 \"min\":1,\"max\":10}",
+                "{\"type\":\"error\",\"message\":\"expected `text`\"}",
+                "{\"type\":\"error\",\"message\":\"enter a number from 1 to 10\"}",
+            ]
+        );
+    }
+}
@@ -0,0 +1,209 @@
+// Inline markup for story text:
+//   *emphasis*
+//   @Stan: at the start of a line marks a dialogue speaker
+//   [[system message]] for text from the game rather than the story
+// A backslash makes the next markup character literal, as in `\*`.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    Plain,
+    Emphasis,
+    Speaker,
+    System,
+}
+
+impl Style {
+    fn ansi_code(self) -> &'static str {
+        match self {
+            Style::Plain => "",
+            Style::Emphasis => "\x1b[1m",
+            Style::Speaker => "\x1b[1;36m",
+            Style::System => "\x1b[33m",
+        }
+    }
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+
+#[derive(Debug, PartialEq)]
+pub struct Span {
+    pub style: Style,
+    pub text: String,
+}
+
+pub fn parse(text: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut rest = text;
+    let mut at_line_start = true;
+    while let Some(c) = rest.chars().next() {
+        let styled = match c {
+            '@' if at_line_start => parse_speaker(rest),
+            '[' if rest.starts_with("[[") => rest[2..]
+                .find("]]")
+                .map(|end| (Style::System, &rest[2..2 + end], 2 + end + 2)),
+            '*' => rest[1..]
+                .find('*')
+                .filter(|end| *end > 0 && !rest[1..].starts_with(char::is_whitespace))
+                .map(|end| (Style::Emphasis, &rest[1..1 + end], 1 + end + 1)),
+            _ => None,
+        };
+        match styled {
+            Some((style, styled_text, consumed)) => {
+                if !plain.is_empty() {
+                    spans.push(Span {
+                        style: Style::Plain,
+                        text: plain.split_off(0),
+                    });
+                }
+                spans.push(Span {
+                    style,
+                    text: styled_text.to_string(),
+                });
+                rest = &rest[consumed..];
+                at_line_start = false;
+            }
+            None => {
+                let mut consumed = c.len_utf8();
+                match rest[consumed..].chars().next() {
+                    Some(escaped) if c == '\\' && "*@[\\".contains(escaped) => {
+                        plain.push(escaped);
+                        consumed += escaped.len_utf8();
+                    }
+                    _ => plain.push(c),
+                }
+                rest = &rest[consumed..];
+                at_line_start = c == '\n';
+            }
+        }
+    }
+    if !plain.is_empty() {
+        spans.push(Span {
+            style: Style::Plain,
+            text: plain,
+        });
+    }
+    spans
+}
+
+// `@Name:` becomes a speaker span of "Name:".
+fn parse_speaker(text: &str) -> Option<(Style, &str, usize)> {
+    let colon = text.find(':')?;
+    let name = &text[1..colon];
+    if name.is_empty() || name.contains(['\n', '*', '[']) {
+        return None;
+    }
+    Some((Style::Speaker, &text[1..colon + 1], colon + 1))
+}
+
+// Makes text show as written, whatever markup characters it has.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if "*@[\\".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub fn render_plain(text: &str) -> String {
+    parse(text).into_iter().map(|span| span.text).collect()
+}
+
+pub fn render_ansi(text: &str) -> String {
+    let mut rendered = String::new();
+    for span in parse(text) {
+        if span.style == Style::Plain {
+            rendered.push_str(&span.text);
+        } else {
+            rendered.push_str(span.style.ansi_code());
+            rendered.push_str(&span.text);
+            rendered.push_str(ANSI_RESET);
+        }
+    }
+    rendered
+}
+
+// Width of text as displayed, skipping ANSI escape sequences.
+pub fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut in_escape = false;
+    for c in text.chars() {
+        if in_escape {
+            in_escape = c != 'm';
+        } else if c == '\x1b' {
+            in_escape = true;
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_markup() {
+        assert_eq!(
+            parse("@Stan: Hello *there*.\n[[Game saved.]]"),
+            vec![
+                Span {
+                    style: Style::Speaker,
+                    text: String::from("Stan:"),
+                },
+                Span {
+                    style: Style::Plain,
+                    text: String::from(" Hello "),
+                },
+                Span {
+                    style: Style::Emphasis,
+                    text: String::from("there"),
+                },
+                Span {
+                    style: Style::Plain,
+                    text: String::from(".\n"),
+                },
+                Span {
+                    style: Style::System,
+                    text: String::from("Game saved."),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_plain_text() {
+        assert_eq!(
+            render_plain("*sniff* blackberries and... steel?"),
+            "sniff blackberries and... steel?"
+        );
+        assert_eq!(
+            render_plain("@Stan: Hi.\n@Stan: Bye."),
+            "Stan: Hi.\nStan: Bye."
+        );
+        assert_eq!(render_plain("3 * 4 = 12, not@home"), "3 * 4 = 12, not@home");
+        assert_eq!(render_plain("3 * 4 * 5"), "3 * 4 * 5");
+        assert_eq!(
+            render_plain("\\*literal\\* \\@Stan: \\[[x]]"),
+            "*literal* @Stan: [[x]]"
+        );
+        assert_eq!(render_plain("[[unclosed"), "[[unclosed");
+        assert_eq!(
+            render_plain(&escape("*A* @B: [[C]] \\")),
+            "*A* @B: [[C]] \\"
+        );
+    }
+
+    #[test]
+    fn renders_ansi_text() {
+        assert_eq!(
+            render_ansi("@Stan: *Hi*"),
+            "\x1b[1;36mStan:\x1b[0m \x1b[1mHi\x1b[0m"
+        );
+        assert_eq!(visible_width(&render_ansi("@Stan: *Hi*")), 8);
+    }
+}
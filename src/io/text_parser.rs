@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Stdin, Stdout, Write};
+
+use crate::choice::Choice;
+use crate::command::Command;
+use crate::io::{markup, Interface, Interrupt};
+
+// Words that carry no meaning when matching input against options.
+const STOP_WORDS: [&str; 12] = [
+    "a", "an", "the", "at", "to", "of", "on", "in", "into", "with", "my", "your",
+];
+
+// Plays with typed commands such as "search mountain" or "go down stairs",
+// matched against the text of each option. Numbered input still works.
+pub struct TextParserInterface<R, W> {
+    input: R,
+    output: W,
+    // Maps each synonym to the word it stands for.
+    synonyms: HashMap<String, String>,
+    // Set once writing fails, e.g. when a player disconnects; the session
+    // then ends at the next prompt.
+    output_failed: bool,
+}
+
+impl TextParserInterface<BufReader<Stdin>, Stdout> {
+    pub fn stdio() -> TextParserInterface<BufReader<Stdin>, Stdout> {
+        TextParserInterface::new(BufReader::new(io::stdin()), io::stdout())
+    }
+}
+
+impl<R: BufRead, W: Write> TextParserInterface<R, W> {
+    pub fn new(input: R, output: W) -> TextParserInterface<R, W> {
+        TextParserInterface {
+            input,
+            output,
+            synonyms: HashMap::new(),
+            output_failed: false,
+        }
+    }
+
+    // Lets players type any of synonyms where option text uses word, e.g.
+    // add_synonyms("head", &["go", "walk"]).
+    pub fn add_synonyms(&mut self, word: &str, synonyms: &[&str]) {
+        for synonym in synonyms {
+            self.synonyms
+                .insert(synonym.to_lowercase(), word.to_lowercase());
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+
+    fn normalize(&self, text: &str) -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+            .map(|word| word.trim_matches('\'').to_lowercase())
+            .filter(|word| !word.is_empty() && !STOP_WORDS.contains(&word.as_str()))
+            .map(|word| self.synonyms.get(&word).cloned().unwrap_or(word))
+            .collect()
+    }
+
+    // Indices of the options that contain every word of input.
+    fn matches(&self, input: &[String], options: &[Vec<String>]) -> Vec<usize> {
+        let mut matching = Vec::new();
+        for (index, option) in options.iter().enumerate() {
+            if input.iter().all(|word| {
+                option
+                    .iter()
+                    .any(|option_word| words_match(word, option_word))
+            }) {
+                matching.push(index);
+            }
+        }
+        matching
+    }
+
+    fn say(&mut self, message: &str) {
+        if !self.output_failed && writeln!(self.output, "{}", message).is_err() {
+            self.output_failed = true;
+        }
+    }
+
+    // Prompts for and reads a line, or None once there is no player left.
+    fn read_line(&mut self) -> Option<String> {
+        if self.output_failed || write!(self.output, "> ").is_err() || self.output.flush().is_err()
+        {
+            self.output_failed = true;
+            return None;
+        }
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) | Err(_) => {
+                self.say("");
+                None
+            }
+            Ok(_) => Some(line),
+        }
+    }
+}
+
+// "stairs" matches "staircase" and "mount" matches "mountain": plurals are
+// ignored and input words may be prefixes of at least three letters.
+fn words_match(input: &str, option: &str) -> bool {
+    let stem = |word: &str| -> String {
+        if word.len() > 3 && word.ends_with('s') && !word.ends_with("ss") {
+            word[..word.len() - 1].to_string()
+        } else {
+            word.to_string()
+        }
+    };
+    let (input, option) = (stem(input), stem(option));
+    input == option || (input.len() >= 3 && option.starts_with(&input))
+}
+
+impl<R: BufRead, W: Write> Interface for TextParserInterface<R, W> {
+    fn write(&mut self, message: &str) {
+        self.say(&markup::render_plain(message));
+    }
+
+    fn choose<T: Choice>(&mut self, mut choices: Vec<T>) -> Result<T, Interrupt> {
+        for (i, choice) in choices.iter().enumerate() {
+            let line = format!("{}) {}", i + 1, markup::render_plain(&choice.describe()));
+            self.say(&line);
+        }
+        let options: Vec<Vec<String>> = choices
+            .iter()
+            .map(|choice| self.normalize(&choice.describe()))
+            .collect();
+        // Narrowed by earlier ambiguous input, so that a clarification like
+        // "stairs" after "head down" only considers the options offered.
+        let mut candidates: Vec<usize> = (0..choices.len()).collect();
+        loop {
+            let line = self.read_line().ok_or(Interrupt::EndOfInput)?;
+            if let Some(command) = Command::parse(&line) {
+                return Err(Interrupt::Command(command));
+            }
+            if let Ok(number) = line.trim().parse::<usize>() {
+                if number >= 1 && number <= choices.len() {
+                    return Ok(choices.swap_remove(number - 1));
+                }
+                let message = format!("Choose a choice from 1 to {}.", choices.len());
+                self.say(&message);
+                continue;
+            }
+            let words = self.normalize(&line);
+            if words.is_empty() {
+                self.say("What do you do?");
+                continue;
+            }
+            let narrowed: Vec<Vec<String>> = candidates
+                .iter()
+                .map(|index| options[*index].clone())
+                .collect();
+            let matching: Vec<usize> = self
+                .matches(&words, &narrowed)
+                .into_iter()
+                .map(|index| candidates[index])
+                .collect();
+            match matching.len() {
+                0 => {
+                    self.say("You can't do that here.");
+                    candidates = (0..choices.len()).collect();
+                }
+                1 => return Ok(choices.swap_remove(matching[0])),
+                _ => {
+                    self.say("Which do you mean?");
+                    for index in &matching {
+                        let line = format!("{}) {}", index + 1, choices[*index].describe());
+                        self.say(&line);
+                    }
+                    candidates = matching;
+                }
+            }
+        }
+    }
+
+    fn ask_text(&mut self, question: &str) -> Result<String, Interrupt> {
+        self.write(question);
+        loop {
+            let line = self.read_line().ok_or(Interrupt::EndOfInput)?;
+            if let Some(command) = Command::parse(&line) {
+                return Err(Interrupt::Command(command));
+            }
+            if !line.trim().is_empty() {
+                return Ok(line.trim().to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct TestChoice(&'static str);
+
+    impl Choice for TestChoice {
+        fn describe(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    fn summit_choices() -> Vec<TestChoice> {
+        vec![
+            TestChoice("Look at the areas surrounding the mountain."),
+            TestChoice("Search the tip of the mountain."),
+            TestChoice("Head down the staircase."),
+            TestChoice("Head down the path."),
+        ]
+    }
+
+    fn interface(input: &str) -> TextParserInterface<&[u8], Vec<u8>> {
+        let mut interface = TextParserInterface::new(input.as_bytes(), Vec::new());
+        interface.add_synonyms("head", &["go", "walk"]);
+        interface
+    }
+
+    #[test]
+    fn matches_typed_commands() {
+        assert_eq!(
+            interface("search mountain\n").choose(summit_choices()),
+            Ok(TestChoice("Search the tip of the mountain."))
+        );
+        assert_eq!(
+            interface("look at areas\n").choose(summit_choices()),
+            Ok(TestChoice("Look at the areas surrounding the mountain."))
+        );
+    }
+
+    #[test]
+    fn uses_synonyms() {
+        assert_eq!(
+            interface("go down stairs\n").choose(summit_choices()),
+            Ok(TestChoice("Head down the staircase."))
+        );
+    }
+
+    #[test]
+    fn accepts_numbers() {
+        assert_eq!(
+            interface("9\n4\n").choose(summit_choices()),
+            Ok(TestChoice("Head down the path."))
+        );
+    }
+
+    #[test]
+    fn asks_for_clarification() {
+        let mut interface = interface("go down\npath\n");
+        assert_eq!(
+            interface.choose(summit_choices()),
+            Ok(TestChoice("Head down the path."))
+        );
+        let output = String::from_utf8(interface.output().clone()).unwrap();
+        assert!(output
+            .contains("Which do you mean?\n3) Head down the staircase.\n4) Head down the path.\n"));
+    }
+
+    #[test]
+    fn rejects_unknown_input() {
+        let mut interface = interface("dance\n");
+        assert_eq!(
+            interface.choose(summit_choices()),
+            Err(Interrupt::EndOfInput)
+        );
+        let output = String::from_utf8(interface.output().clone()).unwrap();
+        assert!(output.contains("You can't do that here."));
+    }
+
+    // Output to a player who has gone away.
+    struct Disconnected;
+
+    impl Write for Disconnected {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+    }
+
+    #[test]
+    fn ends_input_when_output_fails() {
+        let mut interface = TextParserInterface::new("search mountain\n".as_bytes(), Disconnected);
+        interface.write("Hello?");
+        assert_eq!(
+            interface.choose(summit_choices()),
+            Err(Interrupt::EndOfInput)
+        );
+        assert_eq!(interface.ask_text("Name?"), Err(Interrupt::EndOfInput));
+    }
+
+    #[test]
+    fn passes_commands_through() {
+        assert_eq!(
+            interface("inventory\n").choose(summit_choices()),
+            Err(Interrupt::Command(Command::Inventory))
+        );
+    }
+
+    #[test]
+    fn asks_for_text_and_numbers() {
+        let mut interface = interface("\n  Ada  \nmany\n4\n");
+        assert_eq!(interface.ask_text("Name?"), Ok(String::from("Ada")));
+        assert_eq!(interface.ask_number("How many?", 1, 5), Ok(4));
+        assert_eq!(
+            String::from_utf8(interface.output().clone()).unwrap(),
+            "Name?\n> > How many?\n> Enter a number from 1 to 5.\nHow many?\n> "
+        );
+    }
+}
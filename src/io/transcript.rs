@@ -0,0 +1,495 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::io::{BufRead, Write};
+
+use crate::choice::Choice;
+use crate::command::Command;
+use crate::io::{markup, Interface, Interrupt, TestInput};
+use crate::json::Json;
+
+// One line of a transcript file:
+//   {"write":"..."}
+//   {"choice":0,"text":"..."}
+//   {"answer":"..."}
+//   {"command":"inventory"}
+// Choices keep the option text so that a replay can tell when the story no
+// longer offers what was chosen.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Entry {
+    Write(String),
+    Choice { index: usize, text: String },
+    // An answer to ask_text or ask_number.
+    Answer(String),
+    Command(Command),
+}
+
+impl Entry {
+    fn to_json(&self) -> Json {
+        match self {
+            Entry::Write(message) => Json::object(vec![("write", Json::string(message))]),
+            Entry::Choice { index, text } => Json::object(vec![
+                ("choice", Json::Number(*index as f64)),
+                ("text", Json::string(text)),
+            ]),
+            Entry::Answer(answer) => Json::object(vec![("answer", Json::string(answer))]),
+            Entry::Command(command) => {
+                Json::object(vec![("command", Json::string(command.name()))])
+            }
+        }
+    }
+
+    fn from_json(json: &Json) -> Result<Entry, String> {
+        if let Some(message) = json.get("write") {
+            return message
+                .as_str()
+                .map(|message| Entry::Write(message.to_string()))
+                .ok_or_else(|| String::from("`write` must be a string"));
+        }
+        if let Some(index) = json.get("choice") {
+            let index = index
+                .as_usize()
+                .ok_or_else(|| String::from("`choice` must be an option index"))?;
+            let text = json
+                .get("text")
+                .and_then(Json::as_str)
+                .ok_or_else(|| String::from("a choice needs its option `text`"))?;
+            return Ok(Entry::Choice {
+                index,
+                text: text.to_string(),
+            });
+        }
+        if let Some(answer) = json.get("answer") {
+            return answer
+                .as_str()
+                .map(|answer| Entry::Answer(answer.to_string()))
+                .ok_or_else(|| String::from("`answer` must be a string"));
+        }
+        if let Some(command) = json.get("command") {
+            let name = command.as_str().unwrap_or("");
+            return Command::parse(name)
+                .map(Entry::Command)
+                .ok_or_else(|| format!("unknown command `{}`", name));
+        }
+        Err(String::from(
+            "expected `write`, `choice`, `answer` or `command`",
+        ))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TranscriptError {
+    // 1-based.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "transcript line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for TranscriptError {}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Transcript {
+    pub entries: Vec<Entry>,
+}
+
+impl Transcript {
+    pub fn load<R: BufRead>(input: R) -> Result<Transcript, TranscriptError> {
+        let mut entries = Vec::new();
+        for (i, line) in input.lines().enumerate() {
+            let error = |message: String| TranscriptError {
+                line: i + 1,
+                message,
+            };
+            let line = line.map_err(|error| error.to_string()).map_err(error)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let json = Json::parse(&line).map_err(|json_error| error(json_error.to_string()))?;
+            entries.push(Entry::from_json(&json).map_err(error)?);
+        }
+        Ok(Transcript { entries })
+    }
+
+    // The recorded choices and commands, for replaying through
+    // TestInterface.
+    #[allow(dead_code)]
+    pub fn inputs(&self) -> VecDeque<TestInput> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Write(_) => None,
+                Entry::Choice { index, .. } => Some(TestInput::Choice(*index)),
+                Entry::Answer(answer) => Some(TestInput::Text(answer.clone())),
+                Entry::Command(command) => Some(TestInput::Command(*command)),
+            })
+            .collect()
+    }
+
+    // Everything written, as TestInterface would have written it.
+    #[allow(dead_code)]
+    pub fn written(&self) -> String {
+        let mut written = String::new();
+        for entry in &self.entries {
+            if let Entry::Write(message) = entry {
+                written.push_str(&markup::render_plain(message));
+            }
+        }
+        written
+    }
+}
+
+// Passes the option index through another interface's choose, so that the
+// decorators below know which option was picked.
+struct Indexed<T> {
+    index: usize,
+    choice: T,
+}
+
+impl<T: Choice> Choice for Indexed<T> {
+    fn describe(&self) -> String {
+        self.choice.describe()
+    }
+}
+
+// Wraps another interface and logs everything written and chosen to log,
+// one entry per line.
+pub struct RecordingInterface<I, W> {
+    inner: I,
+    log: W,
+}
+
+impl<I: Interface, W: Write> RecordingInterface<I, W> {
+    pub fn new(inner: I, log: W) -> RecordingInterface<I, W> {
+        RecordingInterface { inner, log }
+    }
+
+    #[allow(dead_code)]
+    pub fn inner(&self) -> &I {
+        &self.inner
+    }
+
+    #[allow(dead_code)]
+    pub fn log(&self) -> &W {
+        &self.log
+    }
+
+    // Flushed per entry so that the log survives a crash.
+    fn record(&mut self, entry: Entry) {
+        writeln!(self.log, "{}", entry.to_json()).expect("failed to write transcript");
+        self.log.flush().expect("failed to flush transcript");
+    }
+
+    fn record_answer<T: ToString>(&mut self, answer: Result<T, Interrupt>) -> Result<T, Interrupt> {
+        match &answer {
+            Ok(answer) => self.record(Entry::Answer(answer.to_string())),
+            Err(Interrupt::Command(command)) => self.record(Entry::Command(*command)),
+            Err(Interrupt::EndOfInput) => {}
+        }
+        answer
+    }
+}
+
+impl<I: Interface, W: Write> Interface for RecordingInterface<I, W> {
+    fn write(&mut self, message: &str) {
+        self.record(Entry::Write(message.to_string()));
+        self.inner.write(message);
+    }
+
+    fn choose<T: Choice>(&mut self, choices: Vec<T>) -> Result<T, Interrupt> {
+        let indexed = choices
+            .into_iter()
+            .enumerate()
+            .map(|(index, choice)| Indexed { index, choice })
+            .collect();
+        match self.inner.choose(indexed) {
+            Ok(chosen) => {
+                self.record(Entry::Choice {
+                    index: chosen.index,
+                    text: chosen.choice.describe(),
+                });
+                Ok(chosen.choice)
+            }
+            Err(Interrupt::Command(command)) => {
+                self.record(Entry::Command(command));
+                Err(Interrupt::Command(command))
+            }
+            Err(Interrupt::EndOfInput) => Err(Interrupt::EndOfInput),
+        }
+    }
+
+    // Questions are recorded as writes, as TestInterface writes them.
+    fn ask_text(&mut self, question: &str) -> Result<String, Interrupt> {
+        self.record(Entry::Write(question.to_string()));
+        let answer = self.inner.ask_text(question);
+        self.record_answer(answer)
+    }
+
+    fn ask_number(&mut self, question: &str, min: i64, max: i64) -> Result<i64, Interrupt> {
+        self.record(Entry::Write(question.to_string()));
+        let answer = self.inner.ask_number(question, min, max);
+        self.record_answer(answer)
+    }
+}
+
+// Wraps another interface and answers its prompts from a transcript, echoing
+// each replayed answer. Once the transcript runs out, or the story offers
+// something other than what was recorded, the inner interface takes over.
+pub struct ReplayInterface<I> {
+    inner: I,
+    inputs: VecDeque<Entry>,
+}
+
+impl<I: Interface> ReplayInterface<I> {
+    pub fn new(inner: I, transcript: Transcript) -> ReplayInterface<I> {
+        let inputs = transcript
+            .entries
+            .into_iter()
+            .filter(|entry| !matches!(entry, Entry::Write(_)))
+            .collect();
+        ReplayInterface { inner, inputs }
+    }
+
+    #[allow(dead_code)]
+    pub fn inner(&self) -> &I {
+        &self.inner
+    }
+
+    #[allow(dead_code)]
+    pub fn is_replaying(&self) -> bool {
+        !self.inputs.is_empty()
+    }
+
+    // The next recorded answer, or None once the transcript has run out or
+    // no longer matches the story.
+    fn next_answer(&mut self, question: &str) -> Option<Result<String, Interrupt>> {
+        match self.inputs.pop_front()? {
+            Entry::Answer(answer) => {
+                self.inner.write(question);
+                self.inner.write(&format!("> {}", answer));
+                Some(Ok(answer))
+            }
+            Entry::Command(command) => {
+                self.inner.write(&format!("> {}", command.name()));
+                Some(Err(Interrupt::Command(command)))
+            }
+            Entry::Choice { text, .. } => {
+                self.stop(&format!("`{}` was chosen here, not answered", text));
+                None
+            }
+            Entry::Write(_) => None,
+        }
+    }
+
+    fn stop(&mut self, reason: &str) {
+        self.inputs.clear();
+        self.inner
+            .write(&format!("[[The replay stops here: {}.]]", reason));
+    }
+}
+
+impl<I: Interface> Interface for ReplayInterface<I> {
+    fn write(&mut self, message: &str) {
+        self.inner.write(message);
+    }
+
+    fn choose<T: Choice>(&mut self, mut choices: Vec<T>) -> Result<T, Interrupt> {
+        match self.inputs.pop_front() {
+            Some(Entry::Choice { index, text }) => {
+                if choices.get(index).map(|choice| choice.describe()) == Some(text.clone()) {
+                    self.inner.write(&format!("> {}", text));
+                    return Ok(choices.swap_remove(index));
+                }
+                self.stop(&format!("option {} is no longer `{}`", index + 1, text));
+                self.inner.choose(choices)
+            }
+            Some(Entry::Answer(answer)) => {
+                self.stop(&format!("`{}` was answered here, not chosen", answer));
+                self.inner.choose(choices)
+            }
+            Some(Entry::Command(command)) => {
+                self.inner.write(&format!("> {}", command.name()));
+                Err(Interrupt::Command(command))
+            }
+            Some(Entry::Write(_)) | None => self.inner.choose(choices),
+        }
+    }
+
+    fn ask_text(&mut self, question: &str) -> Result<String, Interrupt> {
+        match self.next_answer(question) {
+            Some(answer) => answer,
+            None => self.inner.ask_text(question),
+        }
+    }
+
+    fn ask_number(&mut self, question: &str, min: i64, max: i64) -> Result<i64, Interrupt> {
+        match self.next_answer(question) {
+            Some(Ok(answer)) => match answer.parse::<i64>() {
+                Ok(number) if number >= min && number <= max => Ok(number),
+                _ => {
+                    self.stop(&format!("`{}` is not from {} to {}", answer, min, max));
+                    self.inner.ask_number(question, min, max)
+                }
+            },
+            Some(Err(interrupt)) => Err(interrupt),
+            None => self.inner.ask_number(question, min, max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::TestInterface;
+
+    #[derive(Debug, PartialEq)]
+    struct TestChoice(&'static str);
+
+    impl Choice for TestChoice {
+        fn describe(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    fn log_text(interface: &RecordingInterface<TestInterface, Vec<u8>>) -> String {
+        String::from_utf8(interface.log().clone()).unwrap()
+    }
+
+    #[test]
+    fn records_writes_choices_and_commands() {
+        let inner = TestInterface::with_inputs(VecDeque::from(vec![
+            TestInput::Command(Command::Stats),
+            TestInput::Choice(1),
+        ]));
+        let mut interface = RecordingInterface::new(inner, Vec::new());
+        interface.write("*Hello*");
+        assert_eq!(
+            interface.choose(vec![TestChoice("Foo"), TestChoice("Bar")]),
+            Err(Interrupt::Command(Command::Stats))
+        );
+        assert_eq!(
+            interface.choose(vec![TestChoice("Foo"), TestChoice("Bar")]),
+            Ok(TestChoice("Bar"))
+        );
+        assert_eq!(interface.inner().written, "Hello");
+        assert_eq!(
+            log_text(&interface),
+            "{\"write\":\"*Hello*\"}\n\
+             {\"command\":\"stats\"}\n\
+             {\"choice\":1,\"text\":\"Bar\"}\n"
+        );
+    }
+
+    #[test]
+    fn loads_transcripts_for_test_interface() {
+        let transcript = Transcript::load(
+            &b"{\"write\":\"*Hi* \"}\n\n{\"choice\":2,\"text\":\"Baz\"}\n\
+               {\"command\":\"quit\"}\n{\"write\":\"Bye\"}\n"[..],
+        )
+        .unwrap();
+        assert_eq!(
+            transcript.inputs(),
+            VecDeque::from(vec![
+                TestInput::Choice(2),
+                TestInput::Command(Command::Quit)
+            ])
+        );
+        assert_eq!(transcript.written(), "Hi Bye");
+    }
+
+    #[test]
+    fn reports_bad_transcript_lines() {
+        let error = Transcript::load(&b"{\"write\":\"Hi\"}\n{\"choice\":1}\n"[..]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "transcript line 2: a choice needs its option `text`"
+        );
+    }
+
+    #[test]
+    fn replays_recorded_choices_then_hands_over() {
+        let transcript = Transcript {
+            entries: vec![
+                Entry::Write(String::from("Hi")),
+                Entry::Choice {
+                    index: 0,
+                    text: String::from("Foo"),
+                },
+            ],
+        };
+        let inner = TestInterface::new(VecDeque::from(vec![1]));
+        let mut interface = ReplayInterface::new(inner, transcript);
+        assert!(interface.is_replaying());
+        assert_eq!(
+            interface.choose(vec![TestChoice("Foo"), TestChoice("Bar")]),
+            Ok(TestChoice("Foo"))
+        );
+        assert!(!interface.is_replaying());
+        assert_eq!(
+            interface.choose(vec![TestChoice("Foo"), TestChoice("Bar")]),
+            Ok(TestChoice("Bar"))
+        );
+        assert_eq!(interface.inner().written, "> Foo");
+    }
+
+    #[test]
+    fn stops_replaying_when_options_change() {
+        let transcript = Transcript {
+            entries: vec![
+                Entry::Choice {
+                    index: 0,
+                    text: String::from("Foo"),
+                },
+                Entry::Choice {
+                    index: 0,
+                    text: String::from("Foo"),
+                },
+            ],
+        };
+        let inner = TestInterface::new(VecDeque::from(vec![1]));
+        let mut interface = ReplayInterface::new(inner, transcript);
+        assert_eq!(
+            interface.choose(vec![TestChoice("Baz"), TestChoice("Bar")]),
+            Ok(TestChoice("Bar"))
+        );
+        assert!(!interface.is_replaying());
+        assert_eq!(
+            interface.inner().written,
+            "The replay stops here: option 1 is no longer `Foo`."
+        );
+    }
+
+    #[test]
+    fn records_and_replays_answers() {
+        let inner = TestInterface::with_inputs(VecDeque::from(vec![
+            TestInput::Text(String::from("Ada")),
+            TestInput::Text(String::from("4")),
+        ]));
+        let mut recording = RecordingInterface::new(inner, Vec::new());
+        assert_eq!(recording.ask_text("Name?"), Ok(String::from("Ada")));
+        assert_eq!(recording.ask_number("How many?", 1, 5), Ok(4));
+        let log = log_text(&recording);
+        assert_eq!(
+            log,
+            "{\"write\":\"Name?\"}\n{\"answer\":\"Ada\"}\n\
+             {\"write\":\"How many?\"}\n{\"answer\":\"4\"}\n"
+        );
+
+        let transcript = Transcript::load(log.as_bytes()).unwrap();
+        let mut replay = ReplayInterface::new(TestInterface::new(VecDeque::new()), transcript);
+        assert_eq!(replay.ask_text("Name?"), Ok(String::from("Ada")));
+        assert_eq!(
+            replay.ask_number("How many?", 1, 3),
+            Err(Interrupt::EndOfInput)
+        );
+        assert_eq!(
+            replay.inner().written,
+            "Name?> AdaHow many?> 4\
+             The replay stops here: `4` is not from 1 to 3.How many?"
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use crate::io::markup::visible_width;
+
+// Reflows text to a width. Runs of lines form paragraphs that are joined and
+// rewrapped; blank lines are kept as they are. Lines starting with whitespace
+// or `|` (tables) are treated as preformatted and left alone.
+pub fn reflow(text: &str, width: usize) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() || is_preformatted(line) {
+            if !paragraph.is_empty() {
+                lines.extend(wrap(&paragraph.join(" "), width, 0));
+                paragraph.clear();
+            }
+            lines.push(line.trim_end().to_string());
+        } else {
+            paragraph.push(line.trim());
+        }
+    }
+    if !paragraph.is_empty() {
+        lines.extend(wrap(&paragraph.join(" "), width, 0));
+    }
+    let mut reflowed = lines.join("\n");
+    if text.ends_with('\n') {
+        reflowed.push('\n');
+    }
+    reflowed
+}
+
+fn is_preformatted(line: &str) -> bool {
+    // Styling escape codes do not count.
+    let mut line = line;
+    while line.starts_with('\x1b') {
+        match line.find('m') {
+            Some(end) => line = &line[end + 1..],
+            None => break,
+        }
+    }
+    line.starts_with(|c: char| c.is_whitespace() || c == '|')
+}
+
+// Greedily wraps a single line of text. Continuation lines are indented by
+// hanging_indent spaces, e.g. to line up under the text of "1) ...".
+pub fn wrap(text: &str, width: usize, hanging_indent: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    let mut at_line_start = true;
+    for word in text.split_whitespace() {
+        let word_width = visible_width(word);
+        if !at_line_start && line_width + 1 + word_width > width {
+            lines.push(line);
+            line = " ".repeat(hanging_indent);
+            line_width = hanging_indent;
+            at_line_start = true;
+        }
+        if !at_line_start {
+            line.push(' ');
+            line_width += 1;
+        }
+        line.push_str(word);
+        line_width += word_width;
+        at_line_start = false;
+    }
+    if !at_line_start || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_lines() {
+        assert_eq!(
+            wrap("the quick brown fox jumps", 10, 0),
+            vec!["the quick", "brown fox", "jumps"]
+        );
+        assert_eq!(
+            wrap("1) the quick brown fox", 12, 3),
+            vec!["1) the quick", "   brown fox"]
+        );
+        assert_eq!(wrap("unbreakable", 4, 0), vec!["unbreakable"]);
+        assert_eq!(wrap("", 4, 0), vec![""]);
+        assert_eq!(
+            wrap("\x1b[1mbold\x1b[0m text", 9, 0),
+            vec!["\x1b[1mbold\x1b[0m text"]
+        );
+    }
+
+    #[test]
+    fn reflows_paragraphs() {
+        assert_eq!(
+            reflow(
+                "\nYou wake up alone\natop a mountain.\n\n\nStan: Hello there, traveler.\n",
+                20
+            ),
+            "\nYou wake up alone\natop a mountain.\n\n\nStan: Hello there,\ntraveler.\n"
+        );
+        assert_eq!(
+            reflow("one two\nthree four five", 80),
+            "one two three four five"
+        );
+    }
+
+    #[test]
+    fn keeps_preformatted_lines() {
+        let table = "| Stat     | Base |\n| Strength |    5 |";
+        assert_eq!(reflow(table, 10), table);
+        assert_eq!(
+            reflow("You are carrying:\n  Note x1\n  Rope x2", 80),
+            "You are carrying:\n  Note x1\n  Rope x2"
+        );
+    }
+}
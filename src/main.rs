@@ -1,8 +1,12 @@
 mod accessible;
 mod character;
 mod choice;
+mod command;
 mod inventory;
 mod io;
+mod json;
+mod project;
+mod server;
 mod stat;
 mod story_graph;
 mod table;
@@ -10,109 +14,359 @@ mod template;
 mod unit;
 mod world;
 
-use character::Character;
-use choice::ConstantChoice;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use io::json_lines::JsonLinesInterface;
+use io::text_parser::TextParserInterface;
+use io::transcript::{RecordingInterface, ReplayInterface, Transcript};
 use io::{Interface, StandardIoInterface};
+use project::{ProjectError, StoryProject};
+use story_graph::dot;
+use story_graph::explorer::{self, Strategy};
+use story_graph::format;
+use story_graph::parser::StoryParseError;
+use story_graph::runner::{self, StoryRunner};
+use story_graph::{
+    StoryChoice, StoryElement, StoryGraph, StoryMetadata, StoryNode, StoryOption, StoryText,
+};
+use template::{RenderMode, Template};
 use world::World;
 
-#[derive(Debug)]
-enum Summit {
-    Gaze,
-    Descend,
-}
-
-impl ConstantChoice for Summit {
-    fn describe_str(&self) -> &str {
-        match self {
-            Summit::Gaze => "Gaze down at the world below.",
-            Summit::Descend => "Follow the narrow path.",
-        }
-    }
+fn text(text: &str) -> StoryElement {
+    StoryElement::Text(StoryText::new(Template::raw_from_str(text)))
 }
 
-struct ExitMarker;
-
-fn enter<I: Interface>(mut interface: I, world: World) -> ExitMarker {
-    interface.write("Welcome to the world!");
-    summit(interface, world)
+fn option(intro_text: &str, result_text: Option<&str>, next_node: Option<&str>) -> StoryOption {
+    StoryOption::new(
+        Template::raw_from_str(intro_text),
+        result_text.map(Template::raw_from_str),
+        next_node.map(String::from),
+    )
 }
 
-fn summit<I: Interface>(mut interface: I, world: World) -> ExitMarker {
-    interface.write(
-        "
+fn story() -> StoryGraph {
+    let mut graph = StoryGraph::new();
+    graph.set_metadata(StoryMetadata {
+        title: Some(String::from("Text Adventurers")),
+        start_node: Some(String::from("Summit")),
+        ..StoryMetadata::default()
+    });
+    graph.add_node(StoryNode::new(
+        String::from("Summit"),
+        vec![
+            text(
+                "
 You find yourself upon the summit of a mountain towering over the landscape.
 The summit is thin strip of smooth obsidian several hundred yards long and
 a few feet tall.",
-    );
-    match interface.choose(vec![Summit::Gaze, Summit::Descend]) {
-        Summit::Gaze => {
-            interface.write(
-                "
+            ),
+            StoryElement::Choice(StoryChoice::new(vec![
+                option(
+                    "Gaze down at the world below.",
+                    Some(
+                        "
 You gaze. To the east is a small port town, then sea as far as the
 eye can see. In all other directions, the forest covering this mountain fade into
 dense marshes. Past the marsh to the south, there are some woods and perhaps a road,
 but they are obscured by fog.",
-            );
-            summit(interface, world)
-        }
-        Summit::Descend => {
-            interface.write("You descend the narrow path. There is a cabin at the halfway point.");
-            outside_cabin(interface, world)
-        }
+                    ),
+                    Some("Summit"),
+                ),
+                option(
+                    "Follow the narrow path.",
+                    Some("You descend the narrow path. There is a cabin at the halfway point."),
+                    Some("OutsideCabin"),
+                ),
+            ])),
+        ],
+    ));
+    graph.add_node(StoryNode::new(
+        String::from("OutsideCabin"),
+        vec![
+            text(
+                "
+The cabin is small but sturdy and well-maintained.",
+            ),
+            StoryElement::Choice(StoryChoice::new(vec![
+                option(
+                    "Try the cabin door.",
+                    Some("The door is unlocked, and you head inside."),
+                    None,
+                ),
+                option(
+                    "Continue up the path to the summit of the mountain.",
+                    None,
+                    Some("Summit"),
+                ),
+                option(
+                    "Follow the path that leads down from the cabin.",
+                    Some("You head down the path"),
+                    None,
+                ),
+            ])),
+            StoryElement::Exit,
+        ],
+    ));
+    graph
+}
+
+// `--record=FILE` logs the session to a transcript; `--replay=FILE` plays a
+// transcript back before handing over to the player.
+fn play<I: Interface>(interface: I, graph: &StoryGraph, world: World) {
+    let flag =
+        |name: &str| std::env::args().find_map(|arg| arg.strip_prefix(name).map(String::from));
+    if let Some(path) = flag("--record=") {
+        let log = match File::create(&path) {
+            Ok(log) => log,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                process::exit(1);
+            }
+        };
+        play_story(RecordingInterface::new(interface, log), graph, world);
+    } else if let Some(path) = flag("--replay=") {
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                process::exit(1);
+            }
+        };
+        let transcript = match Transcript::load(BufReader::new(file)) {
+            Ok(transcript) => transcript,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                process::exit(1);
+            }
+        };
+        play_story(ReplayInterface::new(interface, transcript), graph, world);
+    } else {
+        play_story(interface, graph, world);
     }
 }
 
-enum OutsideCabin {
-    GoInside,
-    Ascend,
-    Descend,
+// `--coverage=FILE` writes which parts of the story were played as JSON;
+// `--save=FILE` is where the save and load commands keep the game, which
+// otherwise lasts only until the story ends.
+fn play_story<I: Interface>(mut interface: I, graph: &StoryGraph, world: World) {
+    interface.write(world.player.stats.print_table().as_str());
+    let mut runner = StoryRunner::start(graph, world);
+    let flag =
+        |name: &str| std::env::args().find_map(|arg| arg.strip_prefix(name).map(String::from));
+    if let Some(path) = flag("--save=") {
+        runner.set_save_file(PathBuf::from(path));
+    }
+    if let Err(error) = runner::run(&mut runner, &mut interface) {
+        interface.write(&format!("[[The story cannot continue: {}.]]", error));
+    }
+    if let Some(path) = flag("--coverage=") {
+        let report = runner.coverage().report(graph);
+        if let Err(error) = fs::write(&path, format!("{}\n", report.to_json())) {
+            eprintln!("{}: {}", path, error);
+            process::exit(1);
+        }
+    }
 }
 
-impl ConstantChoice for OutsideCabin {
-    fn describe_str(&self) -> &str {
-        match self {
-            OutsideCabin::GoInside => "Try the cabin door.",
-            OutsideCabin::Ascend => "Continue up the path to the summit of the mountain.",
-            OutsideCabin::Descend => "Follow the path that leads down from the cabin.",
+// `fmt FILE...` rewrites story files in the canonical format; with
+// `--check` it only lists the files that would change.
+fn format_files(args: &[String]) {
+    let check = args.iter().any(|arg| arg == "--check");
+    let mut failed = false;
+    for path in args.iter().filter(|arg| !arg.starts_with("--")) {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                failed = true;
+                continue;
+            }
+        };
+        let formatted = match format::format_source(&source) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                report_parse_errors(path, &errors);
+                failed = true;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
         }
+        if check {
+            println!("{}", path);
+            failed = true;
+        } else if let Err(error) = fs::write(path, formatted) {
+            eprintln!("{}: {}", path, error);
+            failed = true;
+        }
+    }
+    if failed {
+        process::exit(1);
     }
 }
 
-fn outside_cabin<I: Interface>(mut interface: I, world: World) -> ExitMarker {
-    interface.write(
-        "
-The cabin is small but sturdy and well-maintained.",
-    );
-    match interface.choose(vec![
-        OutsideCabin::GoInside,
-        OutsideCabin::Ascend,
-        OutsideCabin::Descend,
-    ]) {
-        OutsideCabin::GoInside => {
-            interface.write("The door is unlocked, and you head inside.");
-            exit(interface, world)
+// `check FILE...` reports every problem in the story files; a `.json`
+// file is checked as a whole project.
+fn check_files(paths: &[String]) {
+    let mut failed = false;
+    for path in paths {
+        if path.ends_with(".json") {
+            if let Err(errors) = StoryProject::load(Path::new(path)) {
+                report_project_errors(path, &errors);
+                failed = true;
+            }
+            continue;
+        }
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                failed = true;
+                continue;
+            }
+        };
+        let graph = match StoryGraph::parse(&source) {
+            Ok(graph) => graph,
+            Err(errors) => {
+                report_parse_errors(path, &errors);
+                failed = true;
+                continue;
+            }
+        };
+        if let Some(start_node) = &graph.metadata().start_node {
+            if graph.find_node(start_node).is_none() {
+                let error = ProjectError::UnknownStartNode(start_node.clone());
+                report_project_errors(path, &[error]);
+                failed = true;
+            }
         }
-        OutsideCabin::Ascend => summit(interface, world),
-        OutsideCabin::Descend => {
-            interface.write("You head down the path");
-            exit(interface, world)
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn report_parse_errors(path: &str, errors: &[StoryParseError]) {
+    for error in errors {
+        eprintln!("{}", error.render(path));
+    }
+    let plural = if errors.len() == 1 { "" } else { "s" };
+    eprintln!("{}: {} error{}", path, errors.len(), plural);
+}
+
+fn report_project_errors(path: &str, errors: &[ProjectError]) {
+    for error in errors {
+        eprintln!("{}", error);
+    }
+    let plural = if errors.len() == 1 { "" } else { "s" };
+    eprintln!("{}: {} error{}", path, errors.len(), plural);
+}
+
+// `--project=FILE` plays the story project described by a manifest instead
+// of the built-in story.
+fn load_story() -> StoryGraph {
+    let path = std::env::args().find_map(|arg| arg.strip_prefix("--project=").map(String::from));
+    let path = match path {
+        Some(path) => path,
+        None => return story(),
+    };
+    match StoryProject::load(Path::new(&path)) {
+        Ok(project) => project.graph,
+        Err(errors) => {
+            report_project_errors(&path, &errors);
+            process::exit(1);
         }
     }
 }
 
-fn exit<I: Interface>(mut interface: I, _world: World) -> ExitMarker {
-    interface.write("Goodbye! Thanks for playing.");
-    ExitMarker
+// `--explore` plays every path through the story and reports problems;
+// `--explore=N` samples N random walks instead.
+fn explore_strategy() -> Option<Strategy> {
+    std::env::args().find_map(|arg| {
+        if arg == "--explore" {
+            return Some(Strategy::Exhaustive);
+        }
+        let walks = arg.strip_prefix("--explore=")?.parse().ok()?;
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |elapsed| elapsed.as_nanos() as u64);
+        Some(Strategy::Sample { walks, seed })
+    })
 }
 
 fn main() {
-    let mut interface = StandardIoInterface {};
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("fmt") => return format_files(&args[2..]),
+        Some("check") => return check_files(&args[2..]),
+        _ => {}
+    }
     let mut world = World::empty();
     world
         .player
         .stats
         .mut_stat(stat::StatKind::Strength)
         .advance(1000);
-    interface.write(world.player.stats.print_table().as_str());
-    enter(interface, world);
+    let mut graph = load_story();
+    // `--strict` stops the story on any template path that fails to resolve.
+    if std::env::args().any(|arg| arg == "--strict") {
+        graph.set_render_mode(RenderMode::Strict);
+    }
+    let start = graph.start_node().unwrap_or_default();
+    // `--serve=PORT` hosts the game on a local port, one session per
+    // connection; `--json` speaks JSON lines for external frontends;
+    // `--parser` plays by typing commands instead of choice numbers.
+    let port = std::env::args().find_map(|arg| {
+        let port = arg.strip_prefix("--serve=")?;
+        match port.parse::<u16>() {
+            Ok(port) if port != 0 => Some(port),
+            _ => {
+                eprintln!("{}: expected a port from 1 to 65535", arg);
+                process::exit(1);
+            }
+        }
+    });
+    // `--dot` prints a map of the story for Graphviz.
+    if std::env::args().any(|arg| arg == "--dot") {
+        print!("{}", dot::to_dot(&graph));
+    } else if let Some(strategy) = explore_strategy() {
+        let report = explorer::explore(&graph, &world, start, strategy);
+        print!("{}", report);
+        if !report.is_clean() {
+            process::exit(1);
+        }
+    } else if let Some(port) = port {
+        let listener = match TcpListener::bind(("127.0.0.1", port)) {
+            Ok(listener) => listener,
+            Err(error) => {
+                eprintln!("port {}: {}", port, error);
+                process::exit(1);
+            }
+        };
+        server::serve(listener, Arc::new(graph), world);
+    } else if std::env::args().any(|arg| arg == "--json") {
+        play(JsonLinesInterface::stdio(), &graph, world);
+    } else if std::env::args().any(|arg| arg == "--parser") {
+        let mut interface = TextParserInterface::stdio();
+        interface.add_synonyms("gaze", &["look", "view"]);
+        interface.add_synonyms("follow", &["take", "walk", "go"]);
+        interface.add_synonyms("try", &["open", "enter"]);
+        play(interface, &graph, world);
+    } else {
+        let mut interface = StandardIoInterface::new();
+        // `--width=N` wraps text to N columns instead of the terminal width.
+        let width = std::env::args()
+            .find_map(|arg| arg.strip_prefix("--width=").and_then(|n| n.parse().ok()));
+        if width.is_some() {
+            interface.set_width(width);
+        }
+        play(interface, &graph, world);
+    }
 }
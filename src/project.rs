@@ -0,0 +1,740 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::character::Character;
+use crate::inventory::{Item, ItemBehavior, KeyData};
+use crate::json::Json;
+use crate::stat::{StatBlock, StatKind, MAX_BASE_VALUE};
+use crate::story_graph::parser::{parse_story, StoryParseError};
+use crate::story_graph::save::Migration;
+use crate::story_graph::{Directive, StoryGraph, StoryMetadata};
+
+// A story written across many files, described by a JSON manifest:
+//
+//     {
+//       "title": "Blade Mountain",
+//       "author": "A. Writer",
+//       "version": "2",
+//       "start": "BladeMountainAwakening",
+//       "files": ["blade_mountain.md"],
+//       "items": "items.json",
+//       "npcs": "npcs.json",
+//       "migrations": "migrations.json",
+//       "include": ["swamp/region.json"]
+//     }
+//
+// Paths are relative to the manifest that lists them. Included manifests
+// add their files and catalogs to the project. The top manifest's title,
+// author, version and start node become the story's metadata; story files
+// may give them in their headers instead, but not differently.
+//
+// Item catalogs list `{"id", "name"}` objects, optionally with `"opens"`
+// (door names, for keys) or `"equipment": true`. NPC catalogs list
+// `{"id", "name"}` objects, optionally with `"stats"` such as
+// `{"Perception": 12}`.
+//
+// The migration table says how saves from earlier versions of the story
+// are brought up to date, one step per version, and where saves whose node
+// is gone resume:
+//
+//     {
+//       "fallback": "BladeMountainSummit",
+//       "versions": [
+//         {"from": "1", "to": "2",
+//          "nodes": {"OldName": "NewName"}, "markers": {"Old": "New"}}
+//       ]
+//     }
+pub struct StoryProject {
+    pub graph: StoryGraph,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ProjectError {
+    Read {
+        path: String,
+        message: String,
+    },
+    Manifest {
+        path: String,
+        message: String,
+    },
+    Parse {
+        path: String,
+        errors: Vec<StoryParseError>,
+    },
+    DuplicateNode {
+        name: String,
+        first_file: String,
+        second_file: String,
+    },
+    ConflictingMetadata {
+        path: String,
+        key: &'static str,
+        project: String,
+        file: String,
+    },
+    UnknownStartNode(String),
+    UnknownFallbackNode(String),
+    UnknownItem {
+        node: String,
+        item: String,
+    },
+    UnknownNpc {
+        node: String,
+        npc: String,
+    },
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProjectError::Read { path, message } => write!(f, "{}: {}", path, message),
+            ProjectError::Manifest { path, message } => write!(f, "{}: {}", path, message),
+            ProjectError::Parse { path, errors } => {
+                let rendered: Vec<String> = errors.iter().map(|error| error.render(path)).collect();
+                write!(f, "{}", rendered.join("\n").trim_end())
+            }
+            ProjectError::DuplicateNode {
+                name,
+                first_file,
+                second_file,
+            } => write!(
+                f,
+                "{}: node `{}` is already defined in {}",
+                second_file, name, first_file
+            ),
+            ProjectError::ConflictingMetadata {
+                path,
+                key,
+                project,
+                file,
+            } => write!(
+                f,
+                "{}: the header gives `{}` as `{}`, but the project gives `{}`",
+                path, key, file, project
+            ),
+            ProjectError::UnknownStartNode(node) => write!(f, "no start node named `{}`", node),
+            ProjectError::UnknownFallbackNode(node) => {
+                write!(f, "no fallback node named `{}`", node)
+            }
+            ProjectError::UnknownItem { node, item } => write!(
+                f,
+                "node `{}` adds item `{}`, which is not in any item catalog",
+                node, item
+            ),
+            ProjectError::UnknownNpc { node, npc } => write!(
+                f,
+                "node `{}` fights `{}`, who is not in any NPC catalog",
+                node, npc
+            ),
+        }
+    }
+}
+
+impl Error for ProjectError {}
+
+impl StoryProject {
+    // Loads the manifest at path and everything it lists, reporting every
+    // problem found.
+    pub fn load(path: &Path) -> Result<StoryProject, Vec<ProjectError>> {
+        StoryProject::load_with(path, &|path| fs::read_to_string(path))
+    }
+
+    // Like load, reading files with read.
+    pub fn load_with(
+        path: &Path,
+        read: &dyn Fn(&Path) -> io::Result<String>,
+    ) -> Result<StoryProject, Vec<ProjectError>> {
+        let mut loader = Loader {
+            read,
+            graph: StoryGraph::new(),
+            node_files: HashMap::new(),
+            manifests: Vec::new(),
+            errors: Vec::new(),
+        };
+        loader.load_manifest(path, true);
+        loader.check_references();
+        if let Some(start_node) = &loader.graph.metadata().start_node {
+            if loader.graph.find_node(start_node).is_none() {
+                let error = ProjectError::UnknownStartNode(start_node.clone());
+                loader.errors.push(error);
+            }
+        }
+        if let Some(fallback) = &loader.graph.migrations().fallback_node {
+            if loader.graph.find_node(fallback).is_none() {
+                let error = ProjectError::UnknownFallbackNode(fallback.clone());
+                loader.errors.push(error);
+            }
+        }
+        if !loader.errors.is_empty() {
+            return Err(loader.errors);
+        }
+        Ok(StoryProject {
+            graph: loader.graph,
+        })
+    }
+}
+
+// Takes `.` and `..` out of path, so that a file reached along two routes
+// has one path.
+fn normalize(path: &Path) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match resolved.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    resolved.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => resolved.push(".."),
+            },
+            component => resolved.push(component),
+        }
+    }
+    resolved
+}
+
+struct Loader<'r> {
+    read: &'r dyn Fn(&Path) -> io::Result<String>,
+    graph: StoryGraph,
+    // The file each node was defined in.
+    node_files: HashMap<String, String>,
+    // Manifests loaded so far, to catch include cycles.
+    manifests: Vec<PathBuf>,
+    errors: Vec<ProjectError>,
+}
+
+impl<'r> Loader<'r> {
+    fn read(&mut self, path: &Path) -> Option<String> {
+        match (self.read)(path) {
+            Ok(text) => Some(text),
+            Err(error) => {
+                self.errors.push(ProjectError::Read {
+                    path: path.display().to_string(),
+                    message: error.to_string(),
+                });
+                None
+            }
+        }
+    }
+
+    fn read_json(&mut self, path: &Path) -> Option<Json> {
+        let text = self.read(path)?;
+        match Json::parse(&text) {
+            Ok(json) => Some(json),
+            Err(error) => {
+                self.manifest_error(path, error.to_string());
+                None
+            }
+        }
+    }
+
+    fn manifest_error(&mut self, path: &Path, message: String) {
+        self.errors.push(ProjectError::Manifest {
+            path: path.display().to_string(),
+            message,
+        });
+    }
+
+    fn load_manifest(&mut self, path: &Path, top: bool) {
+        let path = &normalize(path);
+        if self.manifests.iter().any(|loaded| loaded == path) {
+            self.manifest_error(path, String::from("is included more than once"));
+            return;
+        }
+        self.manifests.push(path.to_path_buf());
+        let manifest = match self.read_json(path) {
+            Some(manifest) => manifest,
+            None => return,
+        };
+        if top {
+            let metadata = StoryMetadata {
+                title: self.path(&manifest, "title", path),
+                author: self.path(&manifest, "author", path),
+                version: self.path(&manifest, "version", path),
+                start_node: self.path(&manifest, "start", path),
+            };
+            self.graph.set_metadata(metadata);
+        }
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        for file in self.paths(&manifest, "files", path) {
+            self.load_story_file(&normalize(&directory.join(file)));
+        }
+        if let Some(items) = self.path(&manifest, "items", path) {
+            self.load_items(&normalize(&directory.join(items)));
+        }
+        if let Some(npcs) = self.path(&manifest, "npcs", path) {
+            self.load_npcs(&normalize(&directory.join(npcs)));
+        }
+        if let Some(migrations) = self.path(&manifest, "migrations", path) {
+            self.load_migrations(&normalize(&directory.join(migrations)));
+        }
+        for include in self.paths(&manifest, "include", path) {
+            self.load_manifest(&directory.join(include), false);
+        }
+    }
+
+    // An optional string member.
+    fn path(&mut self, manifest: &Json, key: &str, path: &Path) -> Option<String> {
+        let value = manifest.get(key)?;
+        match value.as_str() {
+            Some(value) => Some(value.to_string()),
+            None => {
+                self.manifest_error(path, format!("`{}` must be a string", key));
+                None
+            }
+        }
+    }
+
+    // An optional list of strings.
+    fn paths(&mut self, manifest: &Json, key: &str, path: &Path) -> Vec<String> {
+        let value = match manifest.get(key) {
+            Some(value) => value,
+            None => return Vec::new(),
+        };
+        let strings: Option<Vec<String>> = value.as_array().and_then(|values| {
+            values
+                .iter()
+                .map(|value| value.as_str().map(String::from))
+                .collect()
+        });
+        match strings {
+            Some(strings) => strings,
+            None => {
+                self.manifest_error(path, format!("`{}` must be a list of paths", key));
+                Vec::new()
+            }
+        }
+    }
+
+    fn load_story_file(&mut self, path: &Path) {
+        let source = match self.read(path) {
+            Some(source) => source,
+            None => return,
+        };
+        let file = path.display().to_string();
+        let (header, nodes) = match parse_story(&source) {
+            Ok(story) => story,
+            Err(errors) => {
+                self.errors.push(ProjectError::Parse { path: file, errors });
+                return;
+            }
+        };
+        self.merge_metadata(&file, header);
+        for mut node in nodes {
+            let name = node.name().to_string();
+            if let Some(first_file) = self.node_files.get(&name) {
+                self.errors.push(ProjectError::DuplicateNode {
+                    name,
+                    first_file: first_file.clone(),
+                    second_file: file.clone(),
+                });
+                continue;
+            }
+            node.set_source_file(file.clone());
+            self.node_files.insert(name, file.clone());
+            self.graph.add_node(node);
+        }
+    }
+
+    // Fills in what the project does not say yet from a file's header.
+    fn merge_metadata(&mut self, file: &str, header: StoryMetadata) {
+        let mut metadata = self.graph.metadata().clone();
+        let fields = [
+            ("Title", &mut metadata.title, header.title),
+            ("Author", &mut metadata.author, header.author),
+            ("Version", &mut metadata.version, header.version),
+            ("Start", &mut metadata.start_node, header.start_node),
+        ];
+        for (key, project, file_value) in fields {
+            match (project.as_ref(), file_value) {
+                (None, file_value) => *project = file_value,
+                (Some(project), Some(file_value)) if *project != file_value => {
+                    self.errors.push(ProjectError::ConflictingMetadata {
+                        path: file.to_string(),
+                        key,
+                        project: project.clone(),
+                        file: file_value,
+                    })
+                }
+                _ => {}
+            }
+        }
+        self.graph.set_metadata(metadata);
+    }
+
+    // The objects of a catalog, each with an id and a name.
+    fn catalog_entries(&mut self, path: &Path) -> Vec<(String, String, Json)> {
+        let catalog = match self.read_json(path) {
+            Some(catalog) => catalog,
+            None => return Vec::new(),
+        };
+        let entries = match catalog.as_array() {
+            Some(entries) => entries,
+            None => {
+                self.manifest_error(path, String::from("a catalog must be a list"));
+                return Vec::new();
+            }
+        };
+        let mut named = Vec::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let id = entry.get("id").and_then(Json::as_str);
+            let name = entry.get("name").and_then(Json::as_str);
+            match (id, name) {
+                (Some(id), Some(name)) => {
+                    named.push((id.to_string(), name.to_string(), entry.clone()))
+                }
+                _ => self.manifest_error(
+                    path,
+                    format!("entry {} needs an `id` and a `name`", index + 1),
+                ),
+            }
+        }
+        named
+    }
+
+    fn load_items(&mut self, path: &Path) {
+        for (id, name, entry) in self.catalog_entries(path) {
+            let mut behaviors = Vec::new();
+            if let Some(doors) = entry.get("opens").and_then(Json::as_array) {
+                let doors = doors
+                    .iter()
+                    .filter_map(|door| door.as_str().map(String::from))
+                    .collect();
+                behaviors.push(ItemBehavior::Key(KeyData::new(doors)));
+            }
+            if entry.get("equipment") == Some(&Json::Bool(true)) {
+                behaviors.push(ItemBehavior::Equipment);
+            }
+            self.graph.add_item(Item::new(id, name, behaviors));
+        }
+    }
+
+    fn load_npcs(&mut self, path: &Path) {
+        for (id, name, entry) in self.catalog_entries(path) {
+            let mut stats = StatBlock::new();
+            if let Some(Json::Object(values)) = entry.get("stats") {
+                for (stat, value) in values {
+                    let kind = StatKind::from_name(stat);
+                    match (kind, value.as_usize()) {
+                        (Some(kind), Some(value)) if value as i64 <= MAX_BASE_VALUE => {
+                            stats.mut_stat(kind).set_base_value(value as i64)
+                        }
+                        _ => self.manifest_error(
+                            path,
+                            format!(
+                                "`{}` of `{}` must be a stat from 0 to {}",
+                                stat, id, MAX_BASE_VALUE
+                            ),
+                        ),
+                    }
+                }
+            }
+            self.graph.add_npc(id, Character::new(name, stats));
+        }
+    }
+
+    fn load_migrations(&mut self, path: &Path) {
+        let table = match self.read_json(path) {
+            Some(table) => table,
+            None => return,
+        };
+        let mut migrations = self.graph.migrations().clone();
+        if let Some(fallback) = self.path(&table, "fallback", path) {
+            migrations.fallback_node = Some(fallback);
+        }
+        let versions = table.get("versions").map(|versions| versions.as_array());
+        let versions = match versions {
+            None => &[][..],
+            Some(Some(versions)) => versions,
+            Some(None) => {
+                self.manifest_error(path, String::from("`versions` must be a list"));
+                &[]
+            }
+        };
+        for (index, version) in versions.iter().enumerate() {
+            let from = version.get("from").and_then(Json::as_str);
+            let to = version.get("to").and_then(Json::as_str);
+            let (from, to) = match (from, to) {
+                (Some(from), Some(to)) => (from, to),
+                _ => {
+                    let message = format!("version {} needs a `from` and a `to`", index + 1);
+                    self.manifest_error(path, message);
+                    continue;
+                }
+            };
+            let nodes = self.renames(version, "nodes", path);
+            let markers = self.renames(version, "markers", path);
+            migrations.steps.push(Migration {
+                from: from.to_string(),
+                to: to.to_string(),
+                nodes,
+                markers,
+            });
+        }
+        self.graph.set_migrations(migrations);
+    }
+
+    // An optional object of old names to new ones.
+    fn renames(&mut self, version: &Json, key: &str, path: &Path) -> HashMap<String, String> {
+        let members = match version.get(key) {
+            None => return HashMap::new(),
+            Some(Json::Object(members)) => members,
+            Some(_) => {
+                self.manifest_error(path, format!("`{}` must map old names to new ones", key));
+                return HashMap::new();
+            }
+        };
+        let mut renames = HashMap::new();
+        for (from, to) in members {
+            match to.as_str() {
+                Some(to) => {
+                    renames.insert(from.clone(), to.to_string());
+                }
+                None => self.manifest_error(path, format!("`{}` must be renamed to a name", from)),
+            }
+        }
+        renames
+    }
+
+    // Items and NPCs the story uses must be in a catalog.
+    fn check_references(&mut self) {
+        for node in self.graph.nodes() {
+            for directive in node.directives() {
+                match directive {
+                    Directive::AddItem(item) if self.graph.item(item).is_none() => {
+                        self.errors.push(ProjectError::UnknownItem {
+                            node: node.name().to_string(),
+                            item: item.clone(),
+                        })
+                    }
+                    Directive::Combat(combat) => {
+                        for (_, npc) in &combat.enemies {
+                            if self.graph.npc(npc).is_none() {
+                                self.errors.push(ProjectError::UnknownNpc {
+                                    node: node.name().to_string(),
+                                    npc: npc.clone(),
+                                });
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(files: &[(&str, &str)]) -> Result<StoryProject, Vec<ProjectError>> {
+        let files: HashMap<PathBuf, String> = files
+            .iter()
+            .map(|(path, text)| (PathBuf::from(path), text.to_string()))
+            .collect();
+        StoryProject::load_with(Path::new("game/story.json"), &|path| {
+            files
+                .get(path)
+                .cloned()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not found"))
+        })
+    }
+
+    fn errors(files: &[(&str, &str)]) -> Vec<String> {
+        match load(files) {
+            Ok(_) => panic!("expected errors"),
+            Err(errors) => errors.iter().map(ProjectError::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn loads_files_and_catalogs() {
+        let project = load(&[
+            (
+                "game/story.json",
+                r#"{"title": "Blade Mountain", "start": "Top",
+                    "files": ["top.md"], "items": "items.json",
+                    "include": ["cabin/region.json"]}"#,
+            ),
+            (
+                "game/top.md",
+                "Top\n----\n\n> 'Take the key.'\n> AddItem CabinKey\n\n> Goto -> Cabin\n",
+            ),
+            (
+                "game/items.json",
+                r#"[{"id": "CabinKey", "name": "a brass key", "opens": ["CabinDoor"]}]"#,
+            ),
+            (
+                "game/cabin/region.json",
+                r#"{"files": ["cabin.md"], "npcs": "npcs.json"}"#,
+            ),
+            (
+                "game/cabin/cabin.md",
+                "Cabin\n----\n\n> Combat {\n> 1x Stan\n> } -> Top Top\n",
+            ),
+            (
+                "game/cabin/npcs.json",
+                r#"[{"id": "Stan", "name": "Stan", "stats": {"Perception": 12}}]"#,
+            ),
+        ])
+        .unwrap_or_else(|errors| panic!("{:?}", errors));
+        assert_eq!(
+            project.graph.metadata().title.as_deref(),
+            Some("Blade Mountain")
+        );
+        assert_eq!(project.graph.start_node(), Some("Top"));
+        assert_eq!(project.graph.node_names(), vec!["Cabin", "Top"]);
+        assert_eq!(
+            project.graph.get_node("Cabin").source_file(),
+            Some("game/cabin/cabin.md")
+        );
+        assert_eq!(
+            project.graph.item("CabinKey").unwrap().name(),
+            "a brass key"
+        );
+        let stan = project.graph.npc("Stan").unwrap();
+        assert_eq!(stan.stats.stat(StatKind::Perception).base_value(), 12);
+    }
+
+    #[test]
+    fn merges_file_headers() {
+        let files = |manifest: &'static str| {
+            vec![
+                ("game/story.json", manifest),
+                (
+                    "game/a.md",
+                    "| Title Blade Mountain\n| Start Side\n\nTop\n----\n\n> Exit\n",
+                ),
+                ("game/b.md", "| Version 2\n\nSide\n----\n\n> Exit\n"),
+            ]
+        };
+        let project = load(&files(
+            r#"{"author": "A. Writer", "files": ["a.md", "b.md"]}"#,
+        ))
+        .unwrap_or_else(|errors| panic!("{:?}", errors));
+        assert_eq!(
+            project.graph.metadata(),
+            &StoryMetadata {
+                title: Some(String::from("Blade Mountain")),
+                author: Some(String::from("A. Writer")),
+                version: Some(String::from("2")),
+                start_node: Some(String::from("Side")),
+            }
+        );
+        assert_eq!(
+            errors(&files(r#"{"version": "3", "files": ["a.md", "b.md"]}"#)),
+            vec!["game/b.md: the header gives `Version` as `2`, but the project gives `3`"]
+        );
+    }
+
+    #[test]
+    fn loads_migrations() {
+        let files = |table: &'static str| {
+            vec![
+                (
+                    "game/story.json",
+                    r#"{"files": ["a.md"], "migrations": "migrations.json"}"#,
+                ),
+                ("game/a.md", "Top\n----\n\n> Exit\n"),
+                ("game/migrations.json", table),
+            ]
+        };
+        let project = load(&files(
+            r#"{"fallback": "Top", "versions": [
+                {"from": "1", "to": "2", "nodes": {"Summit": "Top"}, "markers": {"A": "B"}}
+            ]}"#,
+        ))
+        .unwrap_or_else(|errors| panic!("{:?}", errors));
+        let migrations = project.graph.migrations();
+        assert_eq!(migrations.fallback_node.as_deref(), Some("Top"));
+        assert_eq!(migrations.steps[0].from, "1");
+        assert_eq!(migrations.steps[0].nodes["Summit"], "Top");
+        assert_eq!(migrations.steps[0].markers["A"], "B");
+        assert_eq!(
+            errors(&files(
+                r#"{"fallback": "Attic", "versions": [{"to": "2"}, {"from": "2", "to": "3", "nodes": {"A": 1}}]}"#
+            )),
+            vec![
+                "game/migrations.json: version 1 needs a `from` and a `to`",
+                "game/migrations.json: `A` must be renamed to a name",
+                "no fallback node named `Attic`",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_duplicate_nodes_across_files() {
+        assert_eq!(
+            errors(&[
+                (
+                    "game/story.json",
+                    r#"{"title": "T", "start": "Top", "files": ["a.md", "b.md"]}"#,
+                ),
+                ("game/a.md", "Top\n----\n\n> Exit\n"),
+                ("game/b.md", "Side\n----\n\n> Exit\n\nTop\n----\n\n> Exit\n"),
+            ]),
+            vec!["game/b.md: node `Top` is already defined in game/a.md"]
+        );
+    }
+
+    #[test]
+    fn reports_include_cycles_through_parent_directories() {
+        assert_eq!(
+            errors(&[
+                (
+                    "game/story.json",
+                    r#"{"files": ["a.md"], "include": ["sub/region.json"]}"#,
+                ),
+                ("game/a.md", "Top\n----\n\n> Exit\n"),
+                ("game/sub/region.json", r#"{"include": ["../story.json"]}"#),
+            ]),
+            vec!["game/story.json: is included more than once"]
+        );
+        assert_eq!(
+            normalize(Path::new("./game/sub/../../other/./a.md")),
+            PathBuf::from("other/a.md")
+        );
+        assert_eq!(normalize(Path::new("../a.md")), PathBuf::from("../a.md"));
+        assert_eq!(normalize(Path::new("/../a.md")), PathBuf::from("/a.md"));
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        assert_eq!(
+            errors(&[
+                (
+                    "game/story.json",
+                    r#"{"title": "T", "start": "Nowhere",
+                        "files": ["a.md", "missing.md"], "include": ["story.json"]}"#,
+                ),
+                (
+                    "game/a.md",
+                    "Top\n----\n\n> 'Take it.'\n> AddItem Key\n\n> Goto Side\n",
+                ),
+            ]),
+            vec![
+                "game/a.md:7:8: expected `->` after `Goto`\n  |\n7 | > Goto Side\n  |        ^",
+                "game/missing.md: not found",
+                "game/story.json: is included more than once",
+                "no start node named `Nowhere`",
+            ]
+        );
+        assert_eq!(
+            errors(&[
+                ("game/story.json", r#"{"title": 3, "files": ["a.md"]}"#),
+                ("game/a.md", "Top\n----\n\n> AddItem Key\n> Exit\n"),
+            ]),
+            vec![
+                "game/story.json: `title` must be a string",
+                "node `Top` adds item `Key`, which is not in any item catalog",
+            ]
+        );
+    }
+}
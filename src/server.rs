@@ -0,0 +1,135 @@
+use std::io::{self, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+use crate::io::text_parser::TextParserInterface;
+use crate::io::Interface;
+use crate::story_graph::runner::{self, StoryRunner};
+use crate::story_graph::StoryGraph;
+use crate::world::World;
+
+// Hosts the story for several players at once. Each connection plays in its
+// own thread with its own copy of world, over the line-based text protocol
+// of TextParserInterface: numbered options are sent one per line, and each
+// line received is an option number, option words, or a meta-command. A
+// connection that fails to be accepted is reported and skipped.
+pub fn serve(listener: TcpListener, graph: Arc<StoryGraph>, world: World) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                eprintln!("failed to accept a connection: {}", error);
+                continue;
+            }
+        };
+        let graph = Arc::clone(&graph);
+        let world = world.clone();
+        thread::spawn(move || {
+            if let Err(error) = play_session(stream, &graph, world) {
+                eprintln!("session ended with an error: {}", error);
+            }
+        });
+    }
+}
+
+fn play_session(stream: TcpStream, graph: &StoryGraph, world: World) -> io::Result<()> {
+    let input = BufReader::new(stream.try_clone()?);
+    let mut interface = TextParserInterface::new(input, stream);
+    let mut runner = StoryRunner::start(graph, world);
+    if let Err(error) = runner::run(&mut runner, &mut interface) {
+        interface.write(&format!("[[The story cannot continue: {}.]]", error));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::story_graph::{StoryChoice, StoryElement, StoryNode, StoryOption, StoryText};
+    use crate::template::Template;
+    use std::io::{BufRead, Read, Write};
+
+    fn door_graph() -> StoryGraph {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
+            "Door".to_string(),
+            vec![
+                StoryElement::Text(StoryText::new(
+                    Template::parse("{player.name} stands at a door.").unwrap(),
+                )),
+                StoryElement::Choice(StoryChoice::new(vec![
+                    StoryOption::new(
+                        Template::raw_from_str("Open it."),
+                        Some(Template::raw_from_str("It creaks open.")),
+                        None,
+                    ),
+                    StoryOption::new(Template::raw_from_str("Knock."), None, None),
+                ])),
+                StoryElement::Exit,
+            ],
+        ));
+        graph
+    }
+
+    fn start_server() -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut world = World::empty();
+        world.player.name = String::from("Ada");
+        thread::spawn(move || serve(listener, Arc::new(door_graph()), world));
+        address
+    }
+
+    fn read_until_prompt(reader: &mut BufReader<TcpStream>) -> String {
+        let mut received = String::new();
+        while !received.ends_with("> ") {
+            let buffer = reader.fill_buf().unwrap();
+            assert!(!buffer.is_empty(), "connection closed early: {}", received);
+            received.push_str(std::str::from_utf8(buffer).unwrap());
+            let length = buffer.len();
+            reader.consume(length);
+        }
+        received
+    }
+
+    #[test]
+    pub fn plays_concurrent_sessions() {
+        let address = start_server();
+        let mut first = TcpStream::connect(address).unwrap();
+        let mut second = TcpStream::connect(address).unwrap();
+        let mut first_reader = BufReader::new(first.try_clone().unwrap());
+        let mut second_reader = BufReader::new(second.try_clone().unwrap());
+
+        let greeting = read_until_prompt(&mut first_reader);
+        assert_eq!(
+            greeting,
+            "Ada stands at a door.\n1) Open it.\n2) Knock.\n> "
+        );
+        assert_eq!(read_until_prompt(&mut second_reader), greeting);
+
+        second.write_all(b"knock\n").unwrap();
+        first.write_all(b"1\n").unwrap();
+        let mut first_rest = String::new();
+        first_reader.read_to_string(&mut first_rest).unwrap();
+        assert_eq!(
+            first_rest,
+            "It creaks open.\nGoodbye! Thanks for playing.\n"
+        );
+        let mut second_rest = String::new();
+        second_reader.read_to_string(&mut second_rest).unwrap();
+        assert_eq!(second_rest, "Goodbye! Thanks for playing.\n");
+    }
+
+    #[test]
+    pub fn ends_session_on_quit() {
+        let address = start_server();
+        let mut stream = TcpStream::connect(address).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        read_until_prompt(&mut reader);
+        stream.write_all(b"quit\n").unwrap();
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "Goodbye! Thanks for playing.\n");
+    }
+}
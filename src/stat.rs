@@ -14,7 +14,7 @@ pub enum StatKind {
 }
 
 const STAT_COUNT: usize = StatKind::FinalStat.index() + 1;
-const MAX_BASE_VALUE: i64 = 30;
+pub const MAX_BASE_VALUE: i64 = 30;
 
 impl StatKind {
     const fn stat_list() -> [StatKind; STAT_COUNT] {
@@ -38,6 +38,21 @@ impl StatKind {
         self as usize
     }
 
+    // Every stat a character has.
+    pub fn all() -> &'static [StatKind] {
+        const STATS: [StatKind; STAT_COUNT] = StatKind::stat_list();
+        &STATS[..StatKind::FinalStat.index()]
+    }
+
+    // Looks a stat up by its display name, e.g. "Perception".
+    pub fn from_name(name: &str) -> Option<StatKind> {
+        StatKind::stat_list()[..StatKind::FinalStat.index()]
+            .iter()
+            .copied()
+            .find(|kind| kind.display_name() == name)
+    }
+
+    #[allow(dead_code)]
     pub fn ticker(self) -> &'static str {
         match self {
             StatKind::Strength => "STR",
@@ -206,12 +221,23 @@ impl Stat {
     }
 
     pub fn set_base_value(&mut self, base_value: i64) {
-        if base_value < 0 || base_value > MAX_BASE_VALUE {
+        if !(0..=MAX_BASE_VALUE).contains(&base_value) {
             panic!("stat value out of range")
         }
         self.base_value = base_value
     }
 
+    // Puts the stat back as it was, e.g. when loading a saved game.
+    pub fn restore(&mut self, base_value: i64, progress: i64) {
+        self.set_base_value(base_value);
+        self.progress = progress.max(0);
+        self.progress_to_next_level = if base_value == 0 {
+            100
+        } else {
+            self.kind.progress_for_level(base_value)
+        };
+    }
+
     pub fn advance(&mut self, amount: i64) {
         self.progress += amount;
         while self.progress >= self.progress_to_next_level {
@@ -225,11 +251,11 @@ impl Stat {
     }
 
     pub fn progress(&self) -> i64 {
-        return self.progress;
+        self.progress
     }
 
     pub fn progress_to_next_level(&self) -> i64 {
-        return self.progress_to_next_level;
+        self.progress_to_next_level
     }
 }
 
@@ -242,8 +268,8 @@ impl StatBlock {
     // Creates a new StatBlock with no progress.
     pub fn new() -> StatBlock {
         let mut stats = [Stat::new(StatKind::FinalStat); STAT_COUNT];
-        for i in 0..stats.len() {
-            stats[i].kind = StatKind::from_usize(i);
+        for (i, stat) in stats.iter_mut().enumerate() {
+            stat.kind = StatKind::from_usize(i);
         }
         StatBlock { stats }
     }
@@ -287,7 +313,7 @@ impl StatBlock {
                 },
                 Column {
                     name: "Modified",
-                    extractor: Box::new(|stat: &Stat| stat.value(&self).to_string()),
+                    extractor: Box::new(|stat: &Stat| stat.value(self).to_string()),
                     alignment: ColumnAlignment::Right,
                 },
                 Column {
@@ -317,6 +343,16 @@ mod tests {
         assert_eq!(block.stat(StatKind::Strength).value(&block), 0);
     }
 
+    #[test]
+    pub fn stats_are_found_by_name() {
+        assert_eq!(
+            StatKind::from_name("Perception").map(StatKind::index),
+            Some(StatKind::Perception.index())
+        );
+        assert!(StatKind::from_name("Sneak").is_none());
+        assert!(StatKind::from_name("___").is_none());
+    }
+
     #[test]
     pub fn stats_have_tickers() {
         assert_eq!(StatKind::Strength.ticker(), "STR");
@@ -344,6 +380,20 @@ mod tests {
         assert_eq!(block.stat(StatKind::Strength).base_value(), 1);
     }
 
+    #[test]
+    pub fn stats_can_be_restored() {
+        let mut advanced = Stat::new(StatKind::Strength);
+        advanced.advance(1000);
+        let mut restored = Stat::new(StatKind::Strength);
+        restored.restore(advanced.base_value(), advanced.progress());
+        assert_eq!(restored.base_value(), advanced.base_value());
+        assert_eq!(restored.progress(), advanced.progress());
+        assert_eq!(
+            restored.progress_to_next_level(),
+            advanced.progress_to_next_level()
+        );
+    }
+
     #[test]
     pub fn checks_with_progress_advance_stats() {
         let mut block = StatBlock::new();
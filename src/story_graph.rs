@@ -1,111 +1,438 @@
+pub mod coverage;
+pub mod dot;
+pub mod explorer;
+pub mod format;
+pub mod parser;
+pub mod runner;
+pub mod save;
+
 use std::collections::HashMap;
 
-use crate::choice::Choice;
-use crate::io::Interface;
-use crate::template::Template;
+use crate::accessible::{AccessPath, Accessible};
+use crate::character::Character;
+use crate::command::{builtin_response, Command, CommandHook, CommandResponse};
+use crate::inventory::Item;
+use crate::io::markup;
+use crate::stat::StatKind;
+use crate::story_graph::save::Migrations;
+use crate::template::{RenderError, RenderMode, Template};
 use crate::world::World;
 
 pub struct StoryGraph {
     node_name_to_node: HashMap<String, StoryNode>,
+    // Node names in the order they were added.
+    node_order: Vec<String>,
+    // Catalogs of what `AddItem` gives and whom `Combat` fights, by id.
+    items: HashMap<String, Item>,
+    npcs: HashMap<String, Character>,
+    metadata: StoryMetadata,
+    migrations: Migrations,
+    render_mode: RenderMode,
+    command_hook: Option<CommandHook>,
 }
 
 impl StoryGraph {
     pub fn new() -> StoryGraph {
         StoryGraph {
             node_name_to_node: HashMap::new(),
+            node_order: Vec::new(),
+            items: HashMap::new(),
+            npcs: HashMap::new(),
+            metadata: StoryMetadata::default(),
+            migrations: Migrations::default(),
+            render_mode: RenderMode::Lenient,
+            command_hook: None,
+        }
+    }
+
+    // Lets the embedding frontend handle meta-commands before the built-in
+    // handling in command::builtin_response.
+    #[allow(dead_code)]
+    pub fn set_command_hook(&mut self, command_hook: CommandHook) {
+        self.command_hook = Some(command_hook);
+    }
+
+    // In RenderMode::Strict, running the story stops with an error on any
+    // template path that fails to resolve, so that test harnesses catch
+    // missing values.
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.render_mode = render_mode;
+    }
+
+    // Parses a story file in the engine_v1 format, reporting every problem
+    // found.
+    pub fn parse(source: &str) -> Result<StoryGraph, Vec<parser::StoryParseError>> {
+        let (metadata, nodes) = parser::parse_story(source)?;
+        let mut graph = StoryGraph::new();
+        graph.set_metadata(metadata);
+        for node in nodes {
+            graph.add_node(node);
         }
+        Ok(graph)
     }
 
+    pub fn metadata(&self) -> &StoryMetadata {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: StoryMetadata) {
+        self.metadata = metadata;
+    }
+
+    pub fn migrations(&self) -> &Migrations {
+        &self.migrations
+    }
+
+    // Used when loading saves from earlier versions of the story.
+    pub fn set_migrations(&mut self, migrations: Migrations) {
+        self.migrations = migrations;
+    }
+
+    // Where new playthroughs begin: the start node the metadata names, or
+    // else the first node added.
+    pub fn start_node(&self) -> Option<&str> {
+        match &self.metadata.start_node {
+            Some(start_node) => Some(start_node),
+            None => self.node_order.first().map(String::as_str),
+        }
+    }
+
+    // A node with the same name as an earlier one replaces it, keeping its
+    // place in the order.
     pub fn add_node(&mut self, node: StoryNode) {
+        if !self.node_name_to_node.contains_key(&node.name) {
+            self.node_order.push(node.name.clone());
+        }
         self.node_name_to_node.insert(node.name.clone(), node);
     }
 
     pub fn get_node(&self, key: &str) -> &StoryNode {
         &self.node_name_to_node[key]
     }
+
+    pub fn find_node(&self, key: &str) -> Option<&StoryNode> {
+        self.node_name_to_node.get(key)
+    }
+
+    // In the order they were added, as they are written in story files.
+    pub fn nodes(&self) -> impl Iterator<Item = &StoryNode> {
+        self.node_order
+            .iter()
+            .map(move |name| &self.node_name_to_node[name])
+    }
+
+    pub fn add_item(&mut self, item: Item) {
+        self.items.insert(item.id().to_string(), item);
+    }
+
+    pub fn item(&self, id: &str) -> Option<&Item> {
+        self.items.get(id)
+    }
+
+    pub fn add_npc(&mut self, id: String, npc: Character) {
+        self.npcs.insert(id, npc);
+    }
+
+    pub fn npc(&self, id: &str) -> Option<&Character> {
+        self.npcs.get(id)
+    }
+
+    // Sorted, so that reports list nodes in a stable order.
+    pub fn node_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.node_name_to_node.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    // Finds template paths that do not name a property of accessible, as
+    // (node name, path) pairs.
+    // Paths that the story assigns with StoryAsk count as known, and so do
+    // `has_item` paths to items in the catalog (or any item, without one).
+    #[allow(dead_code)]
+    pub fn unknown_paths(&self, accessible: &dyn Accessible) -> Vec<(&str, &AccessPath)> {
+        let assigned: Vec<&str> = self
+            .node_name_to_node
+            .values()
+            .flat_map(|node| &node.elements)
+            .filter_map(|element| match element {
+                StoryElement::Ask(ask) => Some(ask.target.as_str()),
+                _ => None,
+            })
+            .collect();
+        let mut unknown = Vec::new();
+        for node in self.node_name_to_node.values() {
+            for template in node.templates() {
+                for path in template.access_paths() {
+                    if !accessible.has_path(path.view())
+                        && !assigned.contains(&path.as_str())
+                        && !self.is_item_check(path)
+                    {
+                        unknown.push((node.name.as_str(), path));
+                    }
+                }
+            }
+        }
+        unknown
+    }
+
+    fn is_item_check(&self, path: &AccessPath) -> bool {
+        match path.as_str().rsplit_once('.') {
+            Some((owner, item)) if owner == "has_item" || owner.ends_with(".has_item") => {
+                self.items.is_empty() || self.items.contains_key(item)
+            }
+            _ => false,
+        }
+    }
+
+    fn respond_to_command(&self, command: Command, world: &World) -> CommandResponse {
+        if let Some(hook) = &self.command_hook {
+            if let Some(response) = hook(command, world) {
+                return response;
+            }
+        }
+        builtin_response(command, world)
+    }
+
+    fn render(&self, template: &Template, world: &World) -> Result<String, RenderError> {
+        template.render_with_mode(world, self.render_mode)
+    }
+}
+
+// What a story says about itself in the header of its file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StoryMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    // Saves record this, so that they can be checked against the story.
+    pub version: Option<String>,
+    pub start_node: Option<String>,
+}
+
+impl StoryMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == StoryMetadata::default()
+    }
+
+    // The title in emphasis, then the author and version if known; None for
+    // untitled stories.
+    pub fn title_screen(&self) -> Option<String> {
+        let title = self.title.as_ref()?;
+        let mut screen = format!("*{}*", markup::escape(title));
+        let byline = match (&self.author, &self.version) {
+            (Some(author), Some(version)) => Some(format!("by {}, version {}", author, version)),
+            (Some(author), None) => Some(format!("by {}", author)),
+            (None, Some(version)) => Some(format!("Version {}", version)),
+            (None, None) => None,
+        };
+        if let Some(byline) = byline {
+            screen.push_str("\n\n");
+            screen.push_str(&markup::escape(&byline));
+        }
+        Some(screen)
+    }
 }
 
 pub struct StoryNode {
     name: String,
     elements: Vec<StoryElement>,
+    // The story file the node was written in; None when built in code.
+    source_file: Option<String>,
 }
 
 impl StoryNode {
+    #[allow(dead_code)]
     pub fn empty_node(name: String) -> StoryNode {
+        StoryNode::new(name, Vec::new())
+    }
+
+    pub fn new(name: String, elements: Vec<StoryElement>) -> StoryNode {
         StoryNode {
             name,
-            elements: Vec::new(),
+            elements,
+            source_file: None,
         }
     }
 
-    pub fn new(name: String, elements: Vec<StoryElement>) -> StoryNode {
-        StoryNode { name, elements }
+    pub fn set_source_file(&mut self, source_file: String) {
+        self.source_file = Some(source_file);
+    }
+
+    pub fn source_file(&self) -> Option<&str> {
+        self.source_file.as_deref()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn elements(&self) -> &[StoryElement] {
+        &self.elements
+    }
+
+    // Every directive in the node, including those of options and those
+    // under conditions.
+    pub fn directives(&self) -> Vec<&Directive> {
+        let mut directives = Vec::new();
+        for element in &self.elements {
+            let element = match element {
+                StoryElement::If(_, conditional) => conditional,
+                element => element,
+            };
+            match element {
+                StoryElement::Directive(directive) => directives.push(directive),
+                StoryElement::Choice(choice) => directives.extend(
+                    choice
+                        .options
+                        .iter()
+                        .filter_map(|option| option.directive.as_ref()),
+                ),
+                _ => {}
+            }
+        }
+        directives
+    }
+
+    fn templates(&self) -> Vec<&Template> {
+        let mut templates = Vec::new();
+        for element in &self.elements {
+            element.collect_templates(&mut templates);
+        }
+        templates
     }
 }
 
 pub enum StoryElement {
     Text(StoryText),
     Choice(StoryChoice),
+    Ask(StoryAsk),
+    Switch(StorySwitch),
+    Directive(Directive),
+    // Runs the element only if the condition holds, otherwise skips it.
+    If(StoryCondition, Box<StoryElement>),
     Exit,
 }
 
 impl StoryElement {
-    fn run<I: Interface>(
-        &self,
-        index: usize,
-        current_node: &StoryNode,
-        graph: &StoryGraph,
-        interface: &mut I,
-        world: World,
-    ) {
+    fn collect_templates<'a>(&'a self, templates: &mut Vec<&'a Template>) {
         match self {
-            StoryElement::Text(text) => text.run(index, current_node, graph, interface, world),
+            StoryElement::Text(text) => templates.push(&text.text),
             StoryElement::Choice(choice) => {
-                choice.run(index, current_node, graph, interface, world)
+                for option in &choice.options {
+                    templates.push(&option.intro_text);
+                    if let Some(result_text) = &option.result_text {
+                        templates.push(result_text);
+                    }
+                }
             }
-            StoryElement::Exit => {
-                interface.write("Goodbye! Thanks for playing.");
+            StoryElement::Ask(ask) => templates.push(&ask.question),
+            StoryElement::Switch(switch) => {
+                templates.extend(switch.cases.iter().map(|case| &case.text))
             }
+            StoryElement::If(_, element) => element.collect_templates(templates),
+            StoryElement::Directive(_) | StoryElement::Exit => {}
         }
     }
 }
 
-pub struct StoryChoice {
-    options: Vec<StoryOption>,
+// Moves the story along or changes the world, as written after `>` in
+// story files.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Directive {
+    Goto(String),
+    Check(StoryCheck),
+    Combat(StoryCombat),
+    // Gives the player the item with this id.
+    AddItem(String),
+    // Sets a marker in the world, for later conditions.
+    Mark(String),
+    // Runs the node, then comes back to the element after the call once the
+    // node runs out of elements.
+    Call(String),
 }
 
-impl StoryChoice {
-    fn run<I: Interface>(
-        &self,
-        index: usize,
-        current_node: &StoryNode,
-        graph: &StoryGraph,
-        interface: &mut I,
-        world: World,
-    ) {
-        let rendered_options = self.render_options(&world);
-        let chosen_rendered = interface.choose(rendered_options);
-        let chosen = &self.options[chosen_rendered.index];
-        if let Some(result_text) = &chosen.result_text {
-            interface.write(&result_text.render(&world));
-        }
-        if let Some(next_node) = &chosen.next_node {
-            let next_node = graph.get_node(next_node.as_str());
-            next_node.elements[0].run(0, next_node, graph, interface, world);
-        } else {
-            current_node.elements[index + 1].run(index + 1, current_node, graph, interface, world);
+// Goes to success_node if the player's skill is at least threshold, else to
+// failure_node. Either way the skill progresses by up to xp.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoryCheck {
+    pub skill: String,
+    pub threshold: i64,
+    pub xp: i64,
+    pub success_node: String,
+    pub failure_node: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoryCombat {
+    // Counts of enemies by id, e.g. (1, "BladeMountainStan").
+    pub enemies: Vec<(u32, String)>,
+    pub victory_node: String,
+    pub defeat_node: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StoryCondition {
+    // Holds if the marker is set, or if it is not when negated.
+    Marker { name: String, negated: bool },
+    // Holds if the player's skill is at least threshold. Nothing is rolled
+    // and nothing progresses.
+    PassiveCheck { skill: String, threshold: i64 },
+}
+
+impl StoryCondition {
+    pub fn holds(&self, world: &World) -> bool {
+        match self {
+            StoryCondition::Marker { name, negated } => world.markers.is_set(name) != *negated,
+            StoryCondition::PassiveCheck { skill, threshold } => {
+                skill_value(world, skill) >= *threshold
+            }
         }
     }
+}
 
-    fn render_options(&self, world: &World) -> Vec<RenderedStoryOption> {
-        let mut rendered = Vec::new();
-        for (index, option) in self.options.iter().enumerate() {
-            rendered.push(RenderedStoryOption {
-                rendered_intro: option.intro_text.render(world),
-                index,
-            });
+// Skills that are not stats have no value yet.
+pub fn skill_value(world: &World, skill: &str) -> i64 {
+    match StatKind::from_name(skill) {
+        Some(kind) => {
+            let stats = &world.player.stats;
+            stats.stat(kind).value(stats)
         }
-        rendered
+        None => 0,
+    }
+}
+
+// Shows the text of the highest case whose threshold the player's skill
+// meets, if any.
+pub struct StorySwitch {
+    skill: String,
+    cases: Vec<SwitchCase>,
+}
+
+pub struct SwitchCase {
+    pub threshold: i64,
+    pub text: Template,
+}
+
+impl StorySwitch {
+    pub fn new(skill: String, cases: Vec<SwitchCase>) -> StorySwitch {
+        StorySwitch { skill, cases }
+    }
+
+    fn case_for(&self, world: &World) -> Option<&SwitchCase> {
+        let value = skill_value(world, &self.skill);
+        self.cases
+            .iter()
+            .filter(|case| case.threshold <= value)
+            .max_by_key(|case| case.threshold)
+    }
+}
+
+pub struct StoryChoice {
+    options: Vec<StoryOption>,
+}
+
+impl StoryChoice {
+    pub fn new(options: Vec<StoryOption>) -> StoryChoice {
+        StoryChoice { options }
     }
 }
 
@@ -113,17 +440,63 @@ impl StoryChoice {
 pub struct StoryOption {
     intro_text: Template,
     result_text: Option<Template>,
-    next_node: Option<String>,
+    // Options whose condition fails are not offered.
+    condition: Option<StoryCondition>,
+    directive: Option<Directive>,
+}
+
+impl StoryOption {
+    // Without a next node, the story continues after the choice.
+    pub fn new(
+        intro_text: Template,
+        result_text: Option<Template>,
+        next_node: Option<String>,
+    ) -> StoryOption {
+        StoryOption::with_directive(intro_text, result_text, next_node.map(Directive::Goto))
+    }
+
+    // The directive runs after the result text. Unless it leaves the node,
+    // the story continues after the choice.
+    pub fn with_directive(
+        intro_text: Template,
+        result_text: Option<Template>,
+        directive: Option<Directive>,
+    ) -> StoryOption {
+        StoryOption {
+            intro_text,
+            result_text,
+            condition: None,
+            directive,
+        }
+    }
+
+    pub fn set_condition(&mut self, condition: StoryCondition) {
+        self.condition = Some(condition);
+    }
+}
+
+// Asks the player a question and stores the answer in the world at target,
+// e.g. `player.name` or `vars.riddle_answer`.
+pub struct StoryAsk {
+    question: Template,
+    kind: AskKind,
+    target: AccessPath,
 }
 
-struct RenderedStoryOption {
-    rendered_intro: String,
-    index: usize,
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AskKind {
+    Text,
+    // A whole number from min to max.
+    Number { min: i64, max: i64 },
 }
 
-impl Choice for RenderedStoryOption {
-    fn describe(&self) -> String {
-        self.rendered_intro.clone()
+impl StoryAsk {
+    pub fn new(question: Template, kind: AskKind, target: AccessPath) -> StoryAsk {
+        StoryAsk {
+            question,
+            kind,
+            target,
+        }
     }
 }
 
@@ -132,26 +505,15 @@ pub struct StoryText {
 }
 
 impl StoryText {
-    fn run<I: Interface>(
-        &self,
-        index: usize,
-        current_node: &StoryNode,
-        graph: &StoryGraph,
-        interface: &mut I,
-        world: World,
-    ) {
-        interface.write(&self.text.render(&world));
-        current_node.elements[index + 1].run(index, current_node, graph, interface, world);
+    pub fn new(text: Template) -> StoryText {
+        StoryText { text }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::VecDeque;
-
     use super::*;
     use crate::accessible::AccessPath;
-    use crate::io::TestInterface;
     use crate::template;
     use crate::template::Template;
     use crate::world::World;
@@ -164,116 +526,96 @@ mod tests {
     }
 
     #[test]
-    pub fn text_nodes_write_text() {
-        let node = StoryNode::new(
+    pub fn finds_unknown_paths() {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
             "FooNode".to_string(),
-            vec![
-                StoryElement::Text(StoryText {
-                    text: Template {
-                        tokens: vec![
-                            template::Token::Text(String::from("player.name: ")),
-                            template::Token::Accessor(AccessPath::from(String::from(
-                                "player.name",
-                            ))),
-                            template::Token::Text(String::from(".\n")),
-                        ],
-                    },
-                }),
-                StoryElement::Exit,
-            ],
-        );
-        let graph = StoryGraph::new();
-        let mut interface = TestInterface::new(VecDeque::new());
-        let world = World::empty();
-        node.elements[0].run(0, &node, &graph, &mut interface, world);
-        assert_eq!(
-            interface.written,
-            "player.name: Player.\nGoodbye! Thanks for playing."
-        );
+            vec![StoryElement::Text(StoryText {
+                text: Template {
+                    tokens: vec![
+                        template::Token::Accessor(AccessPath::from(String::from("player.name"))),
+                        template::Token::Accessor(AccessPath::from(String::from("player.nmae"))),
+                    ],
+                },
+            })],
+        ));
+        let unknown = graph.unknown_paths(&World::empty());
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].0, "FooNode");
+        assert_eq!(unknown[0].1.as_str(), "player.nmae");
     }
 
     #[test]
-    pub fn choices_without_a_next_node_run() {
-        let node = StoryNode::new(
+    pub fn knows_item_checks_from_the_catalog() {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
             "FooNode".to_string(),
-            vec![
-                StoryElement::Choice(StoryChoice {
-                    options: vec![
-                        StoryOption {
-                            intro_text: Template::raw_from_str("Foo\n"),
-                            result_text: None,
-                            next_node: None,
-                        },
-                        StoryOption {
-                            intro_text: Template::raw_from_str("Bar\n"),
-                            result_text: Some(Template::raw_from_str("Baz\n")),
-                            next_node: None,
-                        },
-                    ],
-                }),
-                StoryElement::Exit,
-            ],
-        );
-        let graph = StoryGraph::new();
-        let mut interface = TestInterface::new(VecDeque::from(vec![1, 0]));
-        let world = World::empty();
-        node.elements[0].run(0, &node, &graph, &mut interface, world);
-        assert_eq!(interface.written, "Baz\nGoodbye! Thanks for playing.");
-        interface.written.clear();
-        let world = World::empty();
-        node.elements[0].run(0, &node, &graph, &mut interface, world);
-        assert_eq!(interface.written, "Goodbye! Thanks for playing.");
+            vec![StoryElement::Text(StoryText::new(
+                Template::parse(
+                    "{if player.has_item.rope}roped{end}{if player.has_item.rpoe}{end}",
+                )
+                .unwrap(),
+            ))],
+        ));
+        assert!(graph.unknown_paths(&World::empty()).is_empty());
+        graph.add_item(Item::new(
+            String::from("rope"),
+            String::from("Rope"),
+            vec![],
+        ));
+        let unknown = graph.unknown_paths(&World::empty());
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].1.as_str(), "player.has_item.rpoe");
     }
 
     #[test]
-    pub fn choices_with_a_next_node_run() {
+    pub fn knows_paths_assigned_by_questions() {
         let mut graph = StoryGraph::new();
         graph.add_node(StoryNode::new(
             "FooNode".to_string(),
             vec![
-                StoryElement::Text(StoryText {
-                    text: Template::raw_from_str("Choose Foo or Bar\n"),
-                }),
-                StoryElement::Choice(StoryChoice {
-                    options: vec![
-                        StoryOption {
-                            intro_text: Template::raw_from_str("Foo\n"),
-                            result_text: Some(Template::raw_from_str("Chose Foo\n")),
-                            next_node: Some(String::from("FooNode")),
-                        },
-                        StoryOption {
-                            intro_text: Template::raw_from_str("Bar\n"),
-                            result_text: None,
-                            next_node: Some(String::from("BarNode")),
-                        },
-                    ],
-                }),
+                StoryElement::Ask(StoryAsk::new(
+                    Template::raw_from_str("What is the answer?"),
+                    AskKind::Text,
+                    AccessPath::from(String::from("vars.answer")),
+                )),
+                StoryElement::Text(StoryText::new(Template::parse("{vars.answer}").unwrap())),
             ],
         ));
-        graph.add_node(StoryNode::new(
-            "BarNode".to_string(),
-            vec![
-                StoryElement::Text(StoryText {
-                    text: Template::raw_from_str("Reached Bar\n"),
-                }),
-                StoryElement::Exit,
-            ],
-        ));
-        let mut interface = TestInterface::new(VecDeque::from(vec![0, 0, 0, 1]));
-        let world = World::empty();
-        let node = graph.get_node("FooNode");
-        node.elements[0].run(0, node, &graph, &mut interface, world);
+        assert!(graph.unknown_paths(&World::empty()).is_empty());
+    }
+
+    #[test]
+    pub fn starts_where_the_metadata_says() {
+        let mut graph = StoryGraph::new();
+        assert_eq!(graph.start_node(), None);
+        graph.add_node(StoryNode::empty_node(String::from("First")));
+        graph.add_node(StoryNode::empty_node(String::from("Second")));
+        assert_eq!(graph.start_node(), Some("First"));
+        graph.set_metadata(StoryMetadata {
+            start_node: Some(String::from("Second")),
+            ..StoryMetadata::default()
+        });
+        assert_eq!(graph.start_node(), Some("Second"));
+    }
+
+    #[test]
+    pub fn shows_title_screens() {
+        let mut metadata = StoryMetadata::default();
+        assert_eq!(metadata.title_screen(), None);
+        metadata.title = Some(String::from("Blade Mountain"));
+        assert_eq!(metadata.title_screen().unwrap(), "*Blade Mountain*");
+        metadata.author = Some(String::from("A. *Writer*"));
+        metadata.version = Some(String::from("2"));
         assert_eq!(
-            interface.written,
-            "Choose Foo or Bar
-Chose Foo
-Choose Foo or Bar
-Chose Foo
-Choose Foo or Bar
-Chose Foo
-Choose Foo or Bar
-Reached Bar
-Goodbye! Thanks for playing."
+            metadata.title_screen().unwrap(),
+            "*Blade Mountain*\n\nby A. \\*Writer\\*, version 2"
         );
     }
+
+    #[test]
+    pub fn can_be_shared_across_threads() {
+        fn assert_shareable<T: Send + Sync>() {}
+        assert_shareable::<StoryGraph>();
+    }
 }
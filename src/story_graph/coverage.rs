@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::json::Json;
+use crate::story_graph::{StoryElement, StoryGraph};
+use crate::table::{Column, ColumnAlignment, Table};
+use crate::world::World;
+
+// Counts how often each node, element and option of a story was visited.
+// Runners collect one each; merge them to cover a whole test suite.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Coverage {
+    nodes: HashMap<String, usize>,
+    // Keyed by node and element index.
+    elements: HashMap<(String, usize), usize>,
+    // Keyed by node, element index and option index.
+    options: HashMap<(String, usize, usize), usize>,
+}
+
+impl Coverage {
+    pub fn record_node(&mut self, node: &str) {
+        *self.nodes.entry(node.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn record_element(&mut self, node: &str, element: usize) {
+        *self
+            .elements
+            .entry((node.to_string(), element))
+            .or_insert(0) += 1;
+    }
+
+    pub fn record_option(&mut self, node: &str, element: usize, option: usize) {
+        *self
+            .options
+            .entry((node.to_string(), element, option))
+            .or_insert(0) += 1;
+    }
+
+    pub fn node_visits(&self, node: &str) -> usize {
+        self.nodes.get(node).copied().unwrap_or(0)
+    }
+
+    pub fn element_runs(&self, node: &str, element: usize) -> usize {
+        self.elements
+            .get(&(node.to_string(), element))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn option_choices(&self, node: &str, element: usize, option: usize) -> usize {
+        self.options
+            .get(&(node.to_string(), element, option))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    #[allow(dead_code)]
+    pub fn merge(&mut self, other: &Coverage) {
+        for (node, count) in &other.nodes {
+            *self.nodes.entry(node.clone()).or_insert(0) += count;
+        }
+        for (key, count) in &other.elements {
+            *self.elements.entry(key.clone()).or_insert(0) += count;
+        }
+        for (key, count) in &other.options {
+            *self.options.entry(key.clone()).or_insert(0) += count;
+        }
+    }
+
+    // Summarizes coverage of graph per story file, in file order.
+    pub fn report(&self, graph: &StoryGraph) -> CoverageReport {
+        let mut files: Vec<FileCoverage> = Vec::new();
+        for name in graph.node_names() {
+            let node = graph.get_node(name);
+            let file = node.source_file().unwrap_or(BUILT_IN).to_string();
+            let index = match files.iter().position(|coverage| coverage.file == file) {
+                Some(index) => index,
+                None => {
+                    files.push(FileCoverage::new(file));
+                    files.len() - 1
+                }
+            };
+            let coverage = &mut files[index];
+            coverage.nodes += 1;
+            if self.node_visits(name) > 0 {
+                coverage.visited_nodes += 1;
+            } else {
+                coverage.unvisited_nodes.push(name.to_string());
+            }
+            for (element_index, element) in node.elements.iter().enumerate() {
+                coverage.elements += 1;
+                if self.element_runs(name, element_index) > 0 {
+                    coverage.run_elements += 1;
+                }
+                let choice = match element {
+                    StoryElement::Choice(choice) => choice,
+                    _ => continue,
+                };
+                for (option_index, option) in choice.options.iter().enumerate() {
+                    coverage.options += 1;
+                    if self.option_choices(name, element_index, option_index) > 0 {
+                        coverage.chosen_options += 1;
+                    } else {
+                        coverage.unchosen_options.push(UnchosenOption {
+                            node: name.to_string(),
+                            element: element_index,
+                            option: option_index,
+                            text: option.intro_text.render(&World::empty()),
+                        });
+                    }
+                }
+            }
+        }
+        files.sort_by(|a, b| a.file.cmp(&b.file));
+        CoverageReport { files }
+    }
+}
+
+// Stands in for the file name of nodes built in code.
+const BUILT_IN: &str = "(built in)";
+
+#[derive(Debug, PartialEq)]
+pub struct UnchosenOption {
+    pub node: String,
+    pub element: usize,
+    pub option: usize,
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FileCoverage {
+    pub file: String,
+    pub nodes: usize,
+    pub visited_nodes: usize,
+    pub elements: usize,
+    pub run_elements: usize,
+    pub options: usize,
+    pub chosen_options: usize,
+    pub unvisited_nodes: Vec<String>,
+    pub unchosen_options: Vec<UnchosenOption>,
+}
+
+impl FileCoverage {
+    fn new(file: String) -> FileCoverage {
+        FileCoverage {
+            file,
+            nodes: 0,
+            visited_nodes: 0,
+            elements: 0,
+            run_elements: 0,
+            options: 0,
+            chosen_options: 0,
+            unvisited_nodes: Vec::new(),
+            unchosen_options: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CoverageReport {
+    pub files: Vec<FileCoverage>,
+}
+
+impl CoverageReport {
+    pub fn print_table(&self) -> String {
+        let fraction = |covered: usize, total: usize| format!("{}/{}", covered, total);
+        let table = Table {
+            columns: vec![
+                Column {
+                    name: "File",
+                    extractor: Box::new(|file: &FileCoverage| file.file.clone()),
+                    alignment: ColumnAlignment::Left,
+                },
+                Column {
+                    name: "Nodes",
+                    extractor: Box::new(move |file: &FileCoverage| {
+                        fraction(file.visited_nodes, file.nodes)
+                    }),
+                    alignment: ColumnAlignment::Right,
+                },
+                Column {
+                    name: "Elements",
+                    extractor: Box::new(move |file: &FileCoverage| {
+                        fraction(file.run_elements, file.elements)
+                    }),
+                    alignment: ColumnAlignment::Right,
+                },
+                Column {
+                    name: "Options",
+                    extractor: Box::new(move |file: &FileCoverage| {
+                        fraction(file.chosen_options, file.options)
+                    }),
+                    alignment: ColumnAlignment::Right,
+                },
+            ],
+        };
+        table.render(self.files.iter())
+    }
+
+    pub fn to_json(&self) -> Json {
+        let files = self
+            .files
+            .iter()
+            .map(|file| {
+                let unvisited = file
+                    .unvisited_nodes
+                    .iter()
+                    .map(|node| Json::string(node))
+                    .collect();
+                let unchosen = file
+                    .unchosen_options
+                    .iter()
+                    .map(|option| {
+                        Json::object(vec![
+                            ("node", Json::string(&option.node)),
+                            ("element", Json::Number(option.element as f64)),
+                            ("option", Json::Number(option.option as f64)),
+                            ("text", Json::string(&option.text)),
+                        ])
+                    })
+                    .collect();
+                Json::object(vec![
+                    ("file", Json::string(&file.file)),
+                    ("nodes", Json::Number(file.nodes as f64)),
+                    ("visited_nodes", Json::Number(file.visited_nodes as f64)),
+                    ("elements", Json::Number(file.elements as f64)),
+                    ("run_elements", Json::Number(file.run_elements as f64)),
+                    ("options", Json::Number(file.options as f64)),
+                    ("chosen_options", Json::Number(file.chosen_options as f64)),
+                    ("unvisited_nodes", Json::Array(unvisited)),
+                    ("unchosen_options", Json::Array(unchosen)),
+                ])
+            })
+            .collect();
+        Json::object(vec![("files", Json::Array(files))])
+    }
+}
+
+// The table, then what is left to cover in each file.
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.print_table())?;
+        for file in &self.files {
+            if file.unvisited_nodes.is_empty() && file.unchosen_options.is_empty() {
+                continue;
+            }
+            writeln!(f, "{}:", file.file)?;
+            for node in &file.unvisited_nodes {
+                writeln!(f, "  never visited {}", node)?;
+            }
+            for option in &file.unchosen_options {
+                writeln!(
+                    f,
+                    "  never chose {} option {}: {}",
+                    option.node,
+                    option.option + 1,
+                    option.text.trim()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::TestInterface;
+    use crate::story_graph::runner::{self, StoryRunner};
+    use crate::story_graph::{StoryChoice, StoryNode, StoryOption, StoryText};
+    use crate::template::Template;
+    use std::collections::VecDeque;
+
+    fn graph() -> StoryGraph {
+        let mut graph = StoryGraph::new();
+        let mut start = StoryNode::new(
+            "Start".to_string(),
+            vec![
+                StoryElement::Text(StoryText::new(Template::raw_from_str("Hi."))),
+                StoryElement::Choice(StoryChoice::new(vec![
+                    StoryOption::new(
+                        Template::raw_from_str("Go."),
+                        None,
+                        Some(String::from("End")),
+                    ),
+                    StoryOption::new(
+                        Template::raw_from_str("Look."),
+                        None,
+                        Some(String::from("View")),
+                    ),
+                ])),
+            ],
+        );
+        start.set_source_file(String::from("start.md"));
+        graph.add_node(start);
+        let mut view = StoryNode::new("View".to_string(), vec![StoryElement::Exit]);
+        view.set_source_file(String::from("start.md"));
+        graph.add_node(view);
+        graph.add_node(StoryNode::new("End".to_string(), vec![StoryElement::Exit]));
+        graph
+    }
+
+    fn play(graph: &StoryGraph, choices: Vec<usize>) -> Coverage {
+        let mut runner = StoryRunner::new(graph, World::empty(), "Start");
+        let mut interface = TestInterface::new(VecDeque::from(choices));
+        runner::run(&mut runner, &mut interface).unwrap();
+        runner.coverage().clone()
+    }
+
+    #[test]
+    fn runners_count_visits() {
+        let graph = graph();
+        let coverage = play(&graph, vec![0]);
+        assert_eq!(coverage.node_visits("Start"), 1);
+        assert_eq!(coverage.node_visits("End"), 1);
+        assert_eq!(coverage.node_visits("View"), 0);
+        assert_eq!(coverage.element_runs("Start", 1), 1);
+        assert_eq!(coverage.option_choices("Start", 1, 0), 1);
+        assert_eq!(coverage.option_choices("Start", 1, 1), 0);
+    }
+
+    #[test]
+    fn reports_per_file() {
+        let graph = graph();
+        let report = play(&graph, vec![0]).report(&graph);
+        assert_eq!(
+            report.to_string(),
+            "| File       | Nodes | Elements | Options |\n\
+             | (built in) |   1/1 |      1/1 |     0/0 |\n\
+             | start.md   |   1/2 |      2/3 |     1/2 |\n\
+             start.md:\n  never visited View\n  never chose Start option 2: Look.\n"
+        );
+        let json = report.to_json();
+        let start = &json.get("files").unwrap().as_array().unwrap()[1];
+        assert_eq!(
+            start.get("unchosen_options").unwrap().to_string(),
+            "[{\"node\":\"Start\",\"element\":1,\"option\":1,\"text\":\"Look.\"}]"
+        );
+    }
+
+    #[test]
+    fn merges_runs() {
+        let graph = graph();
+        let mut coverage = play(&graph, vec![0]);
+        coverage.merge(&play(&graph, vec![1]));
+        assert_eq!(coverage.node_visits("Start"), 2);
+        let report = coverage.report(&graph);
+        assert!(report
+            .files
+            .iter()
+            .all(|file| file.unvisited_nodes.is_empty() && file.unchosen_options.is_empty()));
+    }
+}
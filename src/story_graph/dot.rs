@@ -0,0 +1,324 @@
+use std::fmt::Write;
+
+use crate::io::markup;
+use crate::story_graph::{Directive, StoryElement, StoryGraph, StoryNode};
+use crate::template::Template;
+use crate::world::World;
+
+// The node that story exits lead to.
+const EXIT: &str = "(exit)";
+
+// How an edge is drawn, by what takes the player along it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EdgeKind {
+    // The player picked an option.
+    Choice,
+    // The story moved on by itself.
+    Continue,
+    // The story moved on because a condition held.
+    Conditional,
+    // The story ran a node and came back.
+    Call,
+}
+
+impl EdgeKind {
+    fn attributes(self) -> &'static str {
+        match self {
+            EdgeKind::Choice => "",
+            EdgeKind::Continue => ", style=dashed",
+            EdgeKind::Conditional => ", style=dotted",
+            EdgeKind::Call => ", style=bold",
+        }
+    }
+}
+
+// Which way a check or a fight went.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Outcome {
+    Always,
+    Success,
+    Failure,
+    Victory,
+    Defeat,
+}
+
+impl Outcome {
+    fn attributes(self) -> &'static str {
+        match self {
+            Outcome::Always => "",
+            Outcome::Success => ", color=darkgreen",
+            Outcome::Failure => ", color=red",
+            Outcome::Victory => ", color=blue, arrowhead=diamond",
+            Outcome::Defeat => ", color=darkorange, arrowhead=diamond",
+        }
+    }
+}
+
+// Renders graph in Graphviz DOT, e.g. for `dot -Tsvg`: one box per node,
+// labeled by name, and one edge per option, labeled by its text. Checks
+// have a green edge for success and a red one for failure, fights a blue
+// one for victory and an orange one for defeat; calls have a bold edge to
+// the node they run.
+pub fn to_dot(graph: &StoryGraph) -> String {
+    let mut dot = String::from("digraph story {\n    node [shape=box];\n");
+    let mut exits = false;
+    for name in graph.node_names() {
+        writeln!(dot, "    {};", quote(name)).unwrap();
+    }
+    for name in graph.node_names() {
+        exits |= write_edges(&mut dot, graph.get_node(name));
+    }
+    if exits {
+        writeln!(dot, "    {} [shape=doublecircle];", quote(EXIT)).unwrap();
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+// Returns whether the node can exit the story.
+fn write_edges(dot: &mut String, node: &StoryNode) -> bool {
+    let mut edge = |to: &str, label: Option<String>, kind: EdgeKind, outcome: Outcome| {
+        let label = match label {
+            Some(label) => format!("label={}", quote(&label)),
+            None => String::from("label=\"\""),
+        };
+        writeln!(
+            dot,
+            "    {} -> {} [{}{}{}];",
+            quote(&node.name),
+            quote(to),
+            label,
+            kind.attributes(),
+            outcome.attributes()
+        )
+        .unwrap();
+    };
+    let kind_for = |label: &Option<String>| match label {
+        Some(_) => EdgeKind::Choice,
+        None => EdgeKind::Continue,
+    };
+    let mut exits = false;
+    // Options that stay in the node carry on through it; whatever ends it
+    // ends them too. None stands for reaching it without a choice.
+    let mut flowing: Vec<Option<String>> = vec![None];
+    for element in &node.elements {
+        match element {
+            StoryElement::Choice(choice) => {
+                let mut carried_on = Vec::new();
+                for option in &choice.options {
+                    let label = option_label(&option.intro_text);
+                    let kind = match option.condition {
+                        Some(_) => EdgeKind::Conditional,
+                        None => EdgeKind::Choice,
+                    };
+                    if let Some(node) = option.directive.as_ref().and_then(called) {
+                        edge(node, Some(label.clone()), EdgeKind::Call, Outcome::Always);
+                    }
+                    let targets = option.directive.as_ref().map(targets).unwrap_or_default();
+                    if targets.is_empty() {
+                        carried_on.push(Some(label));
+                        continue;
+                    }
+                    for (to, outcome) in targets {
+                        edge(to, Some(label.clone()), kind, outcome);
+                    }
+                }
+                flowing = carried_on;
+            }
+            StoryElement::Directive(directive) => {
+                if let Some(node) = called(directive) {
+                    edge(node, None, EdgeKind::Call, Outcome::Always);
+                }
+                let targets = targets(directive);
+                if targets.is_empty() {
+                    continue;
+                }
+                for label in &flowing {
+                    for (to, outcome) in &targets {
+                        edge(to, label.clone(), kind_for(label), *outcome);
+                    }
+                }
+                return exits;
+            }
+            StoryElement::If(_, conditional) => match &**conditional {
+                StoryElement::Directive(directive) => {
+                    if let Some(node) = called(directive) {
+                        edge(node, None, EdgeKind::Call, Outcome::Always);
+                    }
+                    for (to, outcome) in targets(directive) {
+                        edge(to, None, EdgeKind::Conditional, outcome);
+                    }
+                }
+                StoryElement::Exit => {
+                    edge(EXIT, None, EdgeKind::Conditional, Outcome::Always);
+                    exits = true;
+                }
+                _ => {}
+            },
+            StoryElement::Exit => {
+                for label in &flowing {
+                    edge(EXIT, label.clone(), kind_for(label), Outcome::Always);
+                }
+                return exits || !flowing.is_empty();
+            }
+            StoryElement::Text(_) | StoryElement::Ask(_) | StoryElement::Switch(_) => {}
+        }
+    }
+    exits
+}
+
+// Where a directive can leave the node for; empty if it stays.
+fn targets(directive: &Directive) -> Vec<(&str, Outcome)> {
+    match directive {
+        Directive::Goto(node) => vec![(node.as_str(), Outcome::Always)],
+        Directive::Check(check) => vec![
+            (check.success_node.as_str(), Outcome::Success),
+            (check.failure_node.as_str(), Outcome::Failure),
+        ],
+        Directive::Combat(combat) => vec![
+            (combat.victory_node.as_str(), Outcome::Victory),
+            (combat.defeat_node.as_str(), Outcome::Defeat),
+        ],
+        // Calls come back to the node.
+        Directive::AddItem(_) | Directive::Mark(_) | Directive::Call(_) => Vec::new(),
+    }
+}
+
+// The node a call runs before coming back.
+fn called(directive: &Directive) -> Option<&str> {
+    match directive {
+        Directive::Call(node) => Some(node),
+        _ => None,
+    }
+}
+
+fn option_label(intro_text: &Template) -> String {
+    markup::render_plain(&intro_text.render(&World::empty()))
+        .trim()
+        .to_string()
+}
+
+// A DOT string literal.
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::story_graph::{StoryChoice, StoryOption, StoryText};
+
+    #[test]
+    fn exports_nodes_and_labeled_edges() {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
+            "Summit".to_string(),
+            vec![
+                StoryElement::Text(StoryText::new(Template::raw_from_str("The top."))),
+                StoryElement::Choice(StoryChoice::new(vec![
+                    StoryOption::new(
+                        Template::raw_from_str("Read the \"note\"."),
+                        None,
+                        Some(String::from("Summit")),
+                    ),
+                    StoryOption::new(
+                        Template::raw_from_str("*Leave.*\n"),
+                        Some(Template::raw_from_str("Bye.")),
+                        None,
+                    ),
+                ])),
+                StoryElement::Exit,
+            ],
+        ));
+        graph.add_node(StoryNode::new(
+            "Cabin".to_string(),
+            vec![StoryElement::Exit],
+        ));
+        assert_eq!(
+            to_dot(&graph),
+            "digraph story {
+    node [shape=box];
+    \"Cabin\";
+    \"Summit\";
+    \"Cabin\" -> \"(exit)\" [label=\"\", style=dashed];
+    \"Summit\" -> \"Summit\" [label=\"Read the \\\"note\\\".\"];
+    \"Summit\" -> \"(exit)\" [label=\"Leave.\"];
+    \"(exit)\" [shape=doublecircle];
+}
+"
+        );
+    }
+
+    #[test]
+    fn leaves_out_the_exit_when_nothing_exits() {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::empty_node(String::from("Lost")));
+        assert_eq!(
+            to_dot(&graph),
+            "digraph story {\n    node [shape=box];\n    \"Lost\";\n}\n"
+        );
+    }
+
+    #[test]
+    fn colors_checks_and_fights_and_dots_conditions() {
+        let graph = StoryGraph::parse(
+            "Path\n----\n\n\
+             > Marker? MetStan\n\
+             > Goto -> Cabin\n\n\
+             > 'Sneak past.'\n\
+             > Check Sneak 15 -> Past Caught\n\n\
+             > 'Fight.'\n\
+             > Combat {\n\
+             > 1x Stan\n\
+             > } -> Won Lost\n",
+        )
+        .unwrap();
+        assert_eq!(
+            to_dot(&graph),
+            "digraph story {
+    node [shape=box];
+    \"Path\";
+    \"Path\" -> \"Cabin\" [label=\"\", style=dotted];
+    \"Path\" -> \"Past\" [label=\"Sneak past.\", color=darkgreen];
+    \"Path\" -> \"Caught\" [label=\"Sneak past.\", color=red];
+    \"Path\" -> \"Won\" [label=\"Fight.\", color=blue, arrowhead=diamond];
+    \"Path\" -> \"Lost\" [label=\"Fight.\", color=darkorange, arrowhead=diamond];
+}
+"
+        );
+    }
+
+    #[test]
+    fn draws_calls_bold_and_carries_on() {
+        let graph = StoryGraph::parse(
+            "Cabin\n----\n\n\
+             > Call ReadNote\n\n\
+             > 'Look again.'\n\
+             > Call ReadNote\n\n\
+             > Exit\n",
+        )
+        .unwrap();
+        assert_eq!(
+            to_dot(&graph),
+            "digraph story {
+    node [shape=box];
+    \"Cabin\";
+    \"Cabin\" -> \"ReadNote\" [label=\"\", style=bold];
+    \"Cabin\" -> \"ReadNote\" [label=\"Look again.\", style=bold];
+    \"Cabin\" -> \"(exit)\" [label=\"Look again.\"];
+    \"(exit)\" [shape=doublecircle];
+}
+"
+        );
+    }
+}
@@ -0,0 +1,529 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::io::TestInput;
+use crate::stat::StatKind;
+use crate::story_graph::runner::{Cursor, PromptOption, Step, StoryError, StoryRunner};
+use crate::story_graph::{AskKind, StoryGraph};
+use crate::world::{Markers, Variables, World};
+
+// A playthrough that runs this many steps without reaching a choice or a
+// question is taken to be stuck in a loop.
+pub const STEP_LIMIT: usize = 1000;
+// Choices made per sampled walk before it is cut short.
+const WALK_LENGTH: usize = 200;
+// What the explorer types when the story asks for text.
+const TEXT_ANSWER: &str = "Explorer";
+
+pub enum Strategy {
+    // Tries every option of every choice. Choices are told apart by where
+    // they are in the story and by what their conditions can test, so loops
+    // are explored once for each state of the world they lead to.
+    Exhaustive,
+    // Picks options at random, which suits stories too large to exhaust.
+    Sample { walks: usize, seed: u64 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Problem {
+    Panic(String),
+    Broken(StoryError),
+    NoOptions,
+    EndlessLoop,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Panic(message) => write!(f, "panicked: {}", message),
+            Problem::Broken(error) => write!(f, "{}", error),
+            Problem::NoOptions => write!(f, "a choice has no options"),
+            Problem::EndlessLoop => write!(f, "ran {} steps without reaching a choice", STEP_LIMIT),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Finding {
+    pub problem: Problem,
+    // The last node reached before the problem, if any.
+    pub node: Option<String>,
+    // Replays the problem through TestInterface::with_inputs.
+    pub inputs: Vec<TestInput>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    pub reached: BTreeSet<String>,
+    // Sorted.
+    pub unreached: Vec<String>,
+    pub findings: Vec<Finding>,
+    pub choice_points: usize,
+    pub playthroughs: usize,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.unreached.is_empty() && self.findings.is_empty()
+    }
+
+    fn add_finding(&mut self, finding: Finding) {
+        let duplicate = self
+            .findings
+            .iter()
+            .any(|found| found.problem == finding.problem && found.node == finding.node);
+        if !duplicate {
+            self.findings.push(finding);
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Explored {} choice points in {} playthroughs, reaching {} nodes.",
+            self.choice_points,
+            self.playthroughs,
+            self.reached.len()
+        )?;
+        if !self.unreached.is_empty() {
+            writeln!(f, "Never reached: {}", self.unreached.join(", "))?;
+        }
+        for finding in &self.findings {
+            let inputs: Vec<String> = finding.inputs.iter().map(describe_input).collect();
+            writeln!(
+                f,
+                "{}: {}\n  after: {}",
+                finding.node.as_deref().unwrap_or("(start)"),
+                finding.problem,
+                inputs.join(" ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Inputs as a player would type them.
+fn describe_input(input: &TestInput) -> String {
+    match input {
+        TestInput::Choice(index) => (index + 1).to_string(),
+        TestInput::Text(text) => format!("{:?}", text),
+        TestInput::Command(command) => command.name().to_string(),
+    }
+}
+
+#[derive(Clone)]
+struct Playthrough<'g> {
+    runner: StoryRunner<'g>,
+    node: Option<String>,
+    inputs: Vec<TestInput>,
+}
+
+enum Stop {
+    Prompt(Vec<PromptOption>),
+    Finished,
+    Problem(Problem),
+}
+
+// A pending choice: where it is, including where the calls under way return
+// to (a scene called from two places leads on differently), and the parts of
+// the world that option conditions test. Stat progress and items are left
+// out, so that loops which only add to them are not explored forever.
+#[derive(PartialEq, Eq, Hash)]
+struct ChoicePoint {
+    cursor: Cursor,
+    calls: Vec<Cursor>,
+    markers: Markers,
+    vars: Variables,
+    stat_levels: Vec<i64>,
+}
+
+// Runs f, reporting a panic as a problem.
+fn guard<T>(f: impl FnOnce() -> T) -> Result<T, Problem> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        let message = payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_default();
+        Problem::Panic(message)
+    })
+}
+
+impl<'g> Playthrough<'g> {
+    fn new(graph: &'g StoryGraph, world: &World, start_node: &str) -> Playthrough<'g> {
+        let mut runner = StoryRunner::new(graph, world.clone(), start_node);
+        runner.set_history_limit(0);
+        Playthrough {
+            runner,
+            node: None,
+            inputs: Vec::new(),
+        }
+    }
+
+    // Steps until the story offers a choice or stops, answering questions
+    // along the way.
+    fn advance(&mut self, graph: &StoryGraph, report: &mut Report) -> Stop {
+        for _ in 0..STEP_LIMIT {
+            if let Some(cursor) = self.runner.cursor() {
+                if graph.find_node(&cursor.node).is_some() {
+                    report.reached.insert(cursor.node.clone());
+                    self.node = Some(cursor.node.clone());
+                }
+            }
+            let runner = &mut self.runner;
+            let step = match guard(|| runner.step()) {
+                Ok(step) => step,
+                Err(problem) => return Stop::Problem(problem),
+            };
+            match step {
+                Step::Output(_) => {}
+                Step::Prompt(options) if options.is_empty() => {
+                    return Stop::Problem(Problem::NoOptions)
+                }
+                Step::Prompt(options) => return Stop::Prompt(options),
+                Step::Ask(question) => {
+                    let answer = match question.kind {
+                        AskKind::Text => TEXT_ANSWER.to_string(),
+                        AskKind::Number { min, .. } => min.to_string(),
+                    };
+                    let runner = &mut self.runner;
+                    let answered = guard(|| {
+                        runner
+                            .answer_text(&answer)
+                            .expect("the explorer answers within range")
+                    });
+                    self.inputs.push(TestInput::Text(answer));
+                    if let Err(problem) = answered {
+                        return Stop::Problem(problem);
+                    }
+                }
+                Step::Finished => return Stop::Finished,
+                Step::Error(error) => return Stop::Problem(Problem::Broken(error)),
+            }
+        }
+        Stop::Problem(Problem::EndlessLoop)
+    }
+
+    fn choice_point(&self) -> ChoicePoint {
+        let world = self.runner.world();
+        ChoicePoint {
+            cursor: self.runner.cursor().expect("prompts have a cursor").clone(),
+            calls: self.runner.calls().to_vec(),
+            markers: world.markers.clone(),
+            vars: world.vars.clone(),
+            stat_levels: StatKind::all()
+                .iter()
+                .map(|kind| world.player.stats.stat(*kind).base_value())
+                .collect(),
+        }
+    }
+
+    fn choose(&mut self, index: usize, option: &PromptOption) -> Result<(), Problem> {
+        let runner = &mut self.runner;
+        let chosen = guard(|| {
+            runner
+                .answer(option.id)
+                .expect("options come from the prompt")
+        });
+        self.inputs.push(TestInput::Choice(index));
+        chosen
+    }
+
+    fn finish(self, problem: Problem, report: &mut Report) {
+        report.add_finding(Finding {
+            problem,
+            node: self.node,
+            inputs: self.inputs,
+        });
+    }
+}
+
+// Plays through the story from start_node with a copy of world, looking for
+// nodes that are never reached and for ways the story can break.
+pub fn explore(graph: &StoryGraph, world: &World, start_node: &str, strategy: Strategy) -> Report {
+    let mut report = Report::default();
+    let mut choice_points = HashSet::new();
+    match strategy {
+        Strategy::Exhaustive => {
+            let mut pending = vec![Playthrough::new(graph, world, start_node)];
+            while let Some(mut playthrough) = pending.pop() {
+                match playthrough.advance(graph, &mut report) {
+                    Stop::Prompt(options) => {
+                        if !choice_points.insert(playthrough.choice_point()) {
+                            continue;
+                        }
+                        // Reversed, so that the first option is explored
+                        // first.
+                        for (index, option) in options.iter().enumerate().rev() {
+                            let mut fork = playthrough.clone();
+                            match fork.choose(index, option) {
+                                Ok(()) => pending.push(fork),
+                                Err(problem) => {
+                                    report.playthroughs += 1;
+                                    fork.finish(problem, &mut report);
+                                }
+                            }
+                        }
+                    }
+                    Stop::Finished => report.playthroughs += 1,
+                    Stop::Problem(problem) => {
+                        report.playthroughs += 1;
+                        playthrough.finish(problem, &mut report);
+                    }
+                }
+            }
+        }
+        Strategy::Sample { walks, seed } => {
+            let mut random = Random::new(seed);
+            for _ in 0..walks {
+                let mut playthrough = Playthrough::new(graph, world, start_node);
+                report.playthroughs += 1;
+                for _ in 0..WALK_LENGTH {
+                    match playthrough.advance(graph, &mut report) {
+                        Stop::Prompt(options) => {
+                            choice_points.insert(playthrough.choice_point());
+                            let index = random.below(options.len());
+                            if let Err(problem) = playthrough.choose(index, &options[index]) {
+                                playthrough.finish(problem, &mut report);
+                                break;
+                            }
+                        }
+                        Stop::Finished => break,
+                        Stop::Problem(problem) => {
+                            playthrough.finish(problem, &mut report);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    report.choice_points = choice_points.len();
+    report.unreached = graph
+        .node_names()
+        .into_iter()
+        .filter(|name| !report.reached.contains(*name))
+        .map(String::from)
+        .collect();
+    report
+}
+
+// xorshift64*: good enough to pick options, and reproducible from a seed.
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn new(seed: u64) -> Random {
+        // The state must never be zero.
+        Random { state: seed | 1 }
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 32) as usize % bound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accessible::AccessPath;
+    use crate::story_graph::runner;
+    use crate::story_graph::{
+        StoryAsk, StoryChoice, StoryElement, StoryNode, StoryOption, StoryText,
+    };
+    use crate::template::{RenderMode, Template};
+
+    fn option(text: &str, next_node: &str) -> StoryOption {
+        StoryOption::new(
+            Template::raw_from_str(text),
+            None,
+            Some(next_node.to_string()),
+        )
+    }
+
+    fn choice(options: Vec<StoryOption>) -> StoryElement {
+        StoryElement::Choice(StoryChoice::new(options))
+    }
+
+    fn broken_graph() -> StoryGraph {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
+            "Start".to_string(),
+            vec![
+                StoryElement::Ask(StoryAsk::new(
+                    Template::raw_from_str("Name?"),
+                    AskKind::Text,
+                    AccessPath::from(String::from("player.name")),
+                )),
+                choice(vec![
+                    option("Loop.", "Start"),
+                    option("Fall.", "Cliff"),
+                    option("Wander.", "Nowhere"),
+                    option("Wait.", "Empty"),
+                    option("Leave.", "End"),
+                ]),
+            ],
+        ));
+        graph.add_node(StoryNode::new(
+            "Cliff".to_string(),
+            vec![StoryElement::Text(StoryText::new(
+                Template::parse("{player.nmae} falls.").unwrap(),
+            ))],
+        ));
+        graph.add_node(StoryNode::new("Empty".to_string(), vec![choice(vec![])]));
+        graph.add_node(StoryNode::new("End".to_string(), vec![StoryElement::Exit]));
+        graph.add_node(StoryNode::new(
+            "Hidden".to_string(),
+            vec![StoryElement::Exit],
+        ));
+        graph
+    }
+
+    #[test]
+    fn finds_unreached_nodes_and_broken_paths() {
+        let report = explore(
+            &broken_graph(),
+            &World::empty(),
+            "Start",
+            Strategy::Exhaustive,
+        );
+        assert_eq!(report.unreached, vec!["Hidden"]);
+        assert_eq!(report.choice_points, 1);
+        let problems: Vec<String> = report
+            .findings
+            .iter()
+            .map(|finding| {
+                format!(
+                    "{} {}",
+                    finding.node.as_deref().unwrap_or(""),
+                    finding.problem
+                )
+            })
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                "Cliff story node `Cliff` ended without a choice or exit",
+                "Start no story node named `Nowhere`",
+                "Empty a choice has no options",
+            ]
+        );
+        assert_eq!(
+            report.findings[1].inputs,
+            vec![
+                TestInput::Text(String::from("Explorer")),
+                TestInput::Choice(2)
+            ]
+        );
+        assert!(!report.is_clean());
+    }
+
+    #[test]
+    fn reports_render_errors_with_inputs_that_replay_them() {
+        let mut graph = broken_graph();
+        graph.set_render_mode(RenderMode::Strict);
+        let report = explore(&graph, &World::empty(), "Start", Strategy::Exhaustive);
+        let finding = &report.findings[0];
+        assert!(matches!(
+            &finding.problem,
+            Problem::Broken(error @ StoryError::Render(node, _))
+                if node == "Cliff" && error.to_string().contains("player.nmae")
+        ));
+
+        let mut interface =
+            crate::io::TestInterface::with_inputs(finding.inputs.iter().cloned().collect());
+        let mut runner = StoryRunner::new(&graph, World::empty(), "Start");
+        let replayed = runner::run(&mut runner, &mut interface);
+        assert_eq!(
+            replayed.err().as_ref(),
+            Some(match &finding.problem {
+                Problem::Broken(error) => error,
+                _ => unreachable!(),
+            })
+        );
+    }
+
+    #[test]
+    fn reports_render_errors_while_answering() {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
+            "Start".to_string(),
+            vec![
+                choice(vec![StoryOption::new(
+                    Template::raw_from_str("Jump."),
+                    Some(Template::parse("{player.nmae} jumps.").unwrap()),
+                    None,
+                )]),
+                StoryElement::Exit,
+            ],
+        ));
+        graph.set_render_mode(RenderMode::Strict);
+        let report = explore(&graph, &World::empty(), "Start", Strategy::Exhaustive);
+        assert_eq!(report.findings.len(), 1);
+        assert!(matches!(
+            &report.findings[0].problem,
+            Problem::Broken(StoryError::Render(node, _)) if node == "Start"
+        ));
+        assert_eq!(report.findings[0].inputs, vec![TestInput::Choice(0)]);
+    }
+
+    #[test]
+    fn explores_choices_again_when_markers_change() {
+        let graph = StoryGraph::parse(
+            "Start\n----\n\n\
+             > ?Marker Warned\n\
+             > 'Heed the warning.'\n\
+             > Goto -> Safe\n\n\
+             > 'Wander.'\n\
+             > Goto -> Warning\n\n\
+             Warning\n----\n\n\
+             > Mark Warned\n\n\
+             > Goto -> Start\n\n\
+             Safe\n----\n\n\
+             > Exit\n",
+        )
+        .unwrap();
+        let report = explore(&graph, &World::empty(), "Start", Strategy::Exhaustive);
+        assert!(report.is_clean(), "{}", report);
+        assert_eq!(report.choice_points, 2);
+    }
+
+    #[test]
+    fn samples_random_walks() {
+        let report = explore(
+            &broken_graph(),
+            &World::empty(),
+            "Start",
+            Strategy::Sample { walks: 50, seed: 7 },
+        );
+        assert_eq!(report.playthroughs, 50);
+        assert_eq!(report.unreached, vec!["Hidden"]);
+        assert_eq!(report.findings.len(), 3);
+        assert!(report
+            .findings
+            .iter()
+            .all(|finding| finding.inputs.len() >= 2));
+    }
+
+    #[test]
+    fn describes_reports() {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
+            "Start".to_string(),
+            vec![choice(vec![option("Go.", "Nowhere")])],
+        ));
+        let report = explore(&graph, &World::empty(), "Start", Strategy::Exhaustive);
+        assert_eq!(
+            report.to_string(),
+            "Explored 1 choice points in 1 playthroughs, reaching 1 nodes.\n\
+             Start: no story node named `Nowhere`\n  after: 1\n"
+        );
+    }
+}
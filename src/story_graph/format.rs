@@ -0,0 +1,263 @@
+use crate::io::wrap::wrap;
+use crate::story_graph::parser::{parse_story, StoryParseError};
+use crate::story_graph::{
+    AskKind, Directive, StoryCondition, StoryElement, StoryGraph, StoryMetadata, StoryNode,
+    StoryOption,
+};
+use crate::template::Template;
+
+// Paragraphs are wrapped to this many columns, `> ` included.
+const WIDTH: usize = 80;
+
+// Writes graph in the canonical engine_v1 format that parser::parse_story
+// reads: `----` underlines, single-quoted options, `Goto -> Node` spacing
+// and paragraphs wrapped at 80 columns. Formatting the output again changes
+// nothing.
+#[allow(dead_code)]
+pub fn format_graph(graph: &StoryGraph) -> String {
+    format_story(graph.metadata(), graph.nodes())
+}
+
+// Normalizes a story file, as the `fmt` command does.
+pub fn format_source(source: &str) -> Result<String, Vec<StoryParseError>> {
+    let (metadata, nodes) = parse_story(source)?;
+    Ok(format_story(&metadata, &nodes))
+}
+
+fn format_story<'a>(
+    metadata: &StoryMetadata,
+    nodes: impl IntoIterator<Item = &'a StoryNode>,
+) -> String {
+    let mut blocks: Vec<String> = nodes.into_iter().map(format_node).collect();
+    if !metadata.is_empty() {
+        blocks.insert(0, format_metadata(metadata));
+    }
+    blocks.join("\n")
+}
+
+fn format_metadata(metadata: &StoryMetadata) -> String {
+    let fields = [
+        ("Title", &metadata.title),
+        ("Author", &metadata.author),
+        ("Version", &metadata.version),
+        ("Start", &metadata.start_node),
+    ];
+    fields
+        .iter()
+        .filter_map(|(key, value)| value.as_ref().map(|value| format!("| {} {}\n", key, value)))
+        .collect()
+}
+
+fn format_node(node: &StoryNode) -> String {
+    let mut blocks = vec![format!("{}\n----\n", node.name)];
+    for element in &node.elements {
+        format_element(element, &mut blocks);
+    }
+    blocks.join("\n")
+}
+
+// Adds the element's blocks, each ending with a newline.
+fn format_element(element: &StoryElement, blocks: &mut Vec<String>) {
+    match element {
+        StoryElement::Text(text) => blocks.push(paragraph(&text.text, "")),
+        StoryElement::Choice(choice) => {
+            blocks.extend(choice.options.iter().map(format_option));
+        }
+        StoryElement::Ask(ask) => {
+            let header = match ask.kind {
+                AskKind::Text => format!("| Ask {}\n", ask.target),
+                AskKind::Number { min, max } => {
+                    format!("| AskNumber {} {} {}\n", min, max, ask.target)
+                }
+            };
+            blocks.push(header + &paragraph(&ask.question, ""));
+        }
+        StoryElement::Switch(switch) => {
+            blocks.push(format!("| SwitchCheck {}\n", switch.skill));
+            for case in &switch.cases {
+                blocks.push(paragraph(&case.text, &format!("{}. ", case.threshold)));
+            }
+        }
+        StoryElement::Directive(directive) => blocks.push(prefixed(&directive_lines(directive))),
+        StoryElement::If(condition, conditional) => match &**conditional {
+            StoryElement::Directive(directive) => {
+                let (kind, args) = condition_words(condition);
+                let mut lines = vec![format!("{}? {}", kind, args)];
+                lines.extend(directive_lines(directive));
+                blocks.push(prefixed(&lines));
+            }
+            StoryElement::Exit => {
+                let (kind, args) = condition_words(condition);
+                blocks.push(prefixed(&[
+                    format!("{}? {}", kind, args),
+                    String::from("Exit"),
+                ]));
+            }
+            conditional => {
+                let (kind, args) = condition_words(condition);
+                blocks.push(format!("? {} {}\n", kind, args));
+                format_element(conditional, blocks);
+            }
+        },
+        StoryElement::Exit => blocks.push(String::from("> Exit\n")),
+    }
+}
+
+fn format_option(option: &StoryOption) -> String {
+    let mut lines = Vec::new();
+    if let Some(condition) = &option.condition {
+        let (kind, args) = condition_words(condition);
+        lines.push(format!("?{} {}", kind, args));
+    }
+    lines.extend(quoted(&option.intro_text));
+    if let Some(result_text) = &option.result_text {
+        lines.extend(quoted(result_text));
+    }
+    if let Some(directive) = &option.directive {
+        lines.extend(directive_lines(directive));
+    }
+    prefixed(&lines)
+}
+
+fn condition_words(condition: &StoryCondition) -> (&'static str, String) {
+    match condition {
+        StoryCondition::Marker { name, negated } => {
+            let negation = if *negated { "!" } else { "" };
+            ("Marker", format!("{}{}", negation, name))
+        }
+        StoryCondition::PassiveCheck { skill, threshold } => {
+            ("PassiveCheck", format!("{} {}", skill, threshold))
+        }
+    }
+}
+
+fn directive_lines(directive: &Directive) -> Vec<String> {
+    match directive {
+        Directive::Goto(node) => vec![format!("Goto -> {}", node)],
+        Directive::Check(check) => {
+            let xp = if check.xp == 0 {
+                String::new()
+            } else {
+                format!(" {}xp", check.xp)
+            };
+            vec![format!(
+                "Check {} {}{} -> {} {}",
+                check.skill, check.threshold, xp, check.success_node, check.failure_node
+            )]
+        }
+        Directive::Combat(combat) => {
+            let mut lines = vec![String::from("Combat {")];
+            for (count, enemy) in &combat.enemies {
+                lines.push(format!("{}x {}", count, enemy));
+            }
+            lines.push(format!(
+                "}} -> {} {}",
+                combat.victory_node, combat.defeat_node
+            ));
+            lines
+        }
+        Directive::AddItem(item) => vec![format!("AddItem {}", item)],
+        Directive::Mark(marker) => vec![format!("Mark {}", marker)],
+        Directive::Call(node) => vec![format!("Call {}", node)],
+    }
+}
+
+// Text wrapped in quotes, to fit after `> `. The parser ends quoted text at
+// the first line ending with the opening quote, so single quotes give way to
+// double quotes when a wrapped line would end early.
+fn quoted(text: &Template) -> Vec<String> {
+    let text = text.to_string();
+    for quote in &['\'', '"'] {
+        let lines = wrap(&format!("{}{}{}", quote, text.trim(), quote), WIDTH - 2, 0);
+        if !lines[..lines.len() - 1]
+            .iter()
+            .any(|line| line.ends_with(*quote))
+        {
+            return lines;
+        }
+    }
+    wrap(&format!("'{}'", text.trim()), WIDTH - 2, 0)
+}
+
+fn paragraph(text: &Template, lead: &str) -> String {
+    let mut lines = wrap(&format!("{}{}", lead, text), WIDTH, 0).join("\n");
+    lines.push('\n');
+    lines
+}
+
+fn prefixed(lines: &[String]) -> String {
+    lines.iter().map(|line| format!("> {}\n", line)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::story_graph::{StoryChoice, StoryText};
+
+    const EXAMPLE: &str = include_str!("../../examples/engine_v1_base.md");
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let formatted = format_source(EXAMPLE).unwrap();
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+        assert!(formatted.starts_with(
+            "| Title Blade Mountain\n| Version 1\n| Start BladeMountainAwakening\n\n\
+             BladeMountainAwakening\n----\n\nYou wake up alone atop a mountain"
+        ));
+        assert!(formatted.contains(
+            "> 'Search the tip of the mountain.'\n\
+             > Check Perception 9 10xp -> BladeMountainNote BladeMountainFailedSearch\n"
+        ));
+        assert!(formatted.contains("> Combat {\n> 1x BladeMountainStan\n> } -> "));
+    }
+
+    #[test]
+    fn normalizes_story_files() {
+        let messy = "|  Start   Top\n| Title A  Story\n\n\n\
+                     Top\n=====\n\
+                     A short line\nand another.\n\n\
+                     ? Marker   !MetStan\n\
+                     Who is Stan?\n\n\
+                     >   \"Leave.\"\n\
+                     > Goto->Bottom\n\
+                     > 'Stay.'\n\
+                     > 'You stay.'\n\n\
+                     > PassiveCheck? Smell 3\n\
+                     > Exit\n";
+        assert_eq!(
+            format_source(messy).unwrap(),
+            "| Title A  Story\n| Start Top\n\n\
+             Top\n----\n\n\
+             A short line and another.\n\n\
+             ? Marker !MetStan\n\n\
+             Who is Stan?\n\n\
+             > 'Leave.'\n\
+             > Goto -> Bottom\n\n\
+             > 'Stay.'\n\
+             > 'You stay.'\n\n\
+             > PassiveCheck? Smell 3\n\
+             > Exit\n"
+        );
+    }
+
+    #[test]
+    fn wraps_long_text() {
+        let long = "word ".repeat(30);
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
+            String::from("Long"),
+            vec![
+                StoryElement::Text(StoryText::new(Template::raw_from_str(&long))),
+                StoryElement::Choice(StoryChoice::new(vec![StoryOption::new(
+                    Template::raw_from_str(&format!("{} travelers' way", "x".repeat(66))),
+                    None,
+                    None,
+                )])),
+            ],
+        ));
+        let formatted = format_graph(&graph);
+        assert!(formatted.lines().all(|line| line.len() <= WIDTH));
+        assert!(formatted.contains("\n> \"xxx"));
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+}
@@ -0,0 +1,1029 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::accessible::AccessPath;
+use crate::story_graph::{
+    AskKind, Directive, StoryAsk, StoryCheck, StoryChoice, StoryCombat, StoryCondition,
+    StoryElement, StoryMetadata, StoryNode, StoryOption, StorySwitch, StoryText, SwitchCase,
+};
+use crate::template::Template;
+
+// A problem in a story file, located so that writers can find it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoryParseError {
+    // Both counted from 1; columns in characters.
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    // The whole line the problem is on.
+    pub source_line: String,
+}
+
+impl StoryParseError {
+    // Shows the problem under the offending line, with a caret at the column:
+    //
+    //     story.md:4:18: expected `->` after Check threshold
+    //       |
+    //     4 | > Check Sneak 15 Hidden
+    //       |                  ^
+    pub fn render(&self, file: &str) -> String {
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        // Tabs are kept so the caret lines up however they are shown.
+        let padding: String = self
+            .source_line
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        format!(
+            "{}:{}:{}: {}\n{} |\n{} | {}\n{} | {}^\n",
+            file,
+            self.line,
+            self.column,
+            self.message,
+            gutter,
+            number,
+            self.source_line,
+            gutter,
+            padding
+        )
+    }
+}
+
+impl fmt::Display for StoryParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for StoryParseError {}
+
+type ParseResult<T> = Result<T, StoryParseError>;
+
+#[derive(Clone, Copy)]
+struct Line<'a> {
+    number: usize,
+    // What is left to parse of source, e.g. without its `>`.
+    text: &'a str,
+    source: &'a str,
+}
+
+impl<'a> Line<'a> {
+    // At the start of what is left to parse.
+    fn error(&self, message: String) -> StoryParseError {
+        self.error_at(self.text, message)
+    }
+
+    // At the start of at, which is part of the line.
+    fn error_at(&self, at: &str, message: String) -> StoryParseError {
+        StoryParseError {
+            line: self.number,
+            column: self.column(at),
+            message,
+            source_line: self.source.to_string(),
+        }
+    }
+
+    // Just past at, for things missing after it.
+    fn error_after(&self, at: &str, message: String) -> StoryParseError {
+        self.error_at(&at[at.len()..], message)
+    }
+
+    fn column(&self, at: &str) -> usize {
+        let start = self.source.as_ptr() as usize;
+        let offset = (at.as_ptr() as usize)
+            .checked_sub(start)
+            .filter(|offset| *offset <= self.source.len())
+            .unwrap_or(0);
+        self.source[..offset].chars().count() + 1
+    }
+}
+
+// The words of a line, with `->` always a word of its own, as in
+// `Goto->Node`.
+struct Words<'a> {
+    line: Line<'a>,
+    words: Vec<&'a str>,
+    next: usize,
+}
+
+impl<'a> Words<'a> {
+    fn new(line: Line<'a>, text: &'a str) -> Words<'a> {
+        let mut words = Vec::new();
+        for word in text.split_whitespace() {
+            let mut rest = word;
+            while let Some(arrow) = rest.find("->") {
+                if arrow > 0 {
+                    words.push(&rest[..arrow]);
+                }
+                words.push(&rest[arrow..arrow + 2]);
+                rest = &rest[arrow + 2..];
+            }
+            if !rest.is_empty() {
+                words.push(rest);
+            }
+        }
+        Words {
+            line,
+            words,
+            next: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.words.get(self.next).copied()
+    }
+
+    // The next word, or an error that says what was expected after the
+    // previous one.
+    fn expect(&mut self, what: &str) -> ParseResult<&'a str> {
+        match self.peek() {
+            Some(word) => {
+                self.next += 1;
+                Ok(word)
+            }
+            None => Err(self.missing(format!("expected {}", what))),
+        }
+    }
+
+    fn number(&mut self, what: &str) -> ParseResult<i64> {
+        let word = self.expect(what)?;
+        word.parse::<i64>().map_err(|_| {
+            self.line
+                .error_at(word, format!("expected {}, found `{}`", what, word))
+        })
+    }
+
+    fn arrow(&mut self, after: &str) -> ParseResult<()> {
+        match self.peek() {
+            Some("->") => {
+                self.next += 1;
+                Ok(())
+            }
+            Some(word) => Err(self
+                .line
+                .error_at(word, format!("expected `->` after {}", after))),
+            None => Err(self.missing(format!("expected `->` after {}", after))),
+        }
+    }
+
+    fn end(&self) -> ParseResult<()> {
+        match self.peek() {
+            Some(word) => Err(self.line.error_at(word, format!("unexpected `{}`", word))),
+            None => Ok(()),
+        }
+    }
+
+    fn missing(&self, message: String) -> StoryParseError {
+        match self.next.checked_sub(1) {
+            Some(last) => self.line.error_after(self.words[last], message),
+            None => self.line.error_after(self.line.text, message),
+        }
+    }
+}
+
+// Parses a story file in the engine_v1 format: an optional header, then
+// nodes named by a line underlined with `----`, each a run of blank-line
+// separated blocks.
+//
+// The header describes the story, one `| Key value` line each:
+//
+//     | Title Blade Mountain
+//     | Author A. Writer
+//     | Version 2
+//     | Start BladeMountainAwakening
+//
+// In nodes:
+//
+// - Paragraphs are text.
+// - `> 'Option text'`, an optional `> 'Result text'` and an optional
+//   directive form an option; options in a row form one choice. A leading
+//   `> ?Marker Name` or `> ?PassiveCheck Skill 15` only offers the option if
+//   it holds.
+// - `> Goto -> Node`, `> Check Skill 9 10xp -> Success Failure`,
+//   `> Combat { ... } -> Victory Defeat`, `> AddItem Id`, `> Mark Name`,
+//   `> Call Node` and `> Exit` are directives, run only if a leading
+//   `> Marker? Name` or `> PassiveCheck? Skill 15` holds.
+// - `| SwitchCheck Skill` is followed by `10. Text` paragraphs, one per
+//   threshold.
+// - `| Ask path` and `| AskNumber 1 20 path` are followed by the question.
+// - `? Marker !Name` or `? PassiveCheck Skill 15` only shows the next
+//   paragraph, switch or question if it holds.
+//
+// A broken block is skipped so that the rest of the file is still checked;
+// all problems are returned, in file order.
+pub fn parse_story(source: &str) -> Result<(StoryMetadata, Vec<StoryNode>), Vec<StoryParseError>> {
+    let lines: Vec<Line> = source
+        .lines()
+        .enumerate()
+        .map(|(index, text)| Line {
+            number: index + 1,
+            text: text.trim(),
+            source: text.trim_end(),
+        })
+        .collect();
+    let mut metadata = StoryMetadata::default();
+    let mut nodes = Vec::new();
+    let mut errors = Vec::new();
+    // The line each node name was first defined on.
+    let mut defined: HashMap<&str, usize> = HashMap::new();
+    let mut header: Option<Line> = None;
+    let mut body_start = 0;
+    let mut index = 0;
+    while index <= lines.len() {
+        if index < lines.len() && !is_header(&lines, index) {
+            index += 1;
+            continue;
+        }
+        let body = &lines[body_start..index];
+        match header {
+            Some(name) => {
+                match defined.get(name.text) {
+                    Some(first) => errors.push(name.error(format!(
+                        "node `{}` is already defined on line {}",
+                        name.text, first
+                    ))),
+                    None => {
+                        defined.insert(name.text, name.number);
+                    }
+                }
+                nodes.push(parse_node(name, body, &mut errors));
+            }
+            None => metadata = parse_metadata(body, &mut errors),
+        }
+        header = lines.get(index).copied();
+        body_start = index + 2;
+        index += 2;
+    }
+    if errors.is_empty() {
+        Ok((metadata, nodes))
+    } else {
+        Err(errors)
+    }
+}
+
+// The `| Key value` lines before the first node.
+fn parse_metadata(lines: &[Line], errors: &mut Vec<StoryParseError>) -> StoryMetadata {
+    let mut metadata = StoryMetadata::default();
+    for line in lines.iter().filter(|line| !line.text.is_empty()) {
+        let rest = match line.text.strip_prefix('|') {
+            Some(rest) => rest.trim_start(),
+            None => {
+                errors
+                    .push(line.error(String::from("expected a node name underlined with `----`")));
+                break;
+            }
+        };
+        if let Err(error) = parse_metadata_line(*line, rest, &mut metadata) {
+            errors.push(error);
+        }
+    }
+    metadata
+}
+
+fn parse_metadata_line(line: Line, rest: &str, metadata: &mut StoryMetadata) -> ParseResult<()> {
+    let mut words = Words::new(line, rest);
+    let key = words.expect("a key after `|`")?;
+    let value = rest[key.len()..].trim();
+    let (field, single_word) = match key {
+        "Title" => (&mut metadata.title, false),
+        "Author" => (&mut metadata.author, false),
+        "Version" => (&mut metadata.version, true),
+        "Start" => (&mut metadata.start_node, true),
+        _ => {
+            return Err(line.error_at(
+                key,
+                format!(
+                    "unknown header key `{}`, expected `Title`, `Author`, `Version` or `Start`",
+                    key
+                ),
+            ))
+        }
+    };
+    if field.is_some() {
+        return Err(line.error_at(key, format!("`{}` is given twice", key)));
+    }
+    if value.is_empty() {
+        return Err(words.missing(format!("expected a value after `{}`", key)));
+    }
+    if single_word {
+        words.expect("a value")?;
+        words.end()?;
+    }
+    *field = Some(value.to_string());
+    Ok(())
+}
+
+// A name followed by an underline of at least two `-` or `=`.
+fn is_header(lines: &[Line], index: usize) -> bool {
+    let name = lines[index].text;
+    let underline = match lines.get(index + 1) {
+        Some(line) => line.text,
+        None => return false,
+    };
+    !name.is_empty()
+        && !name.starts_with(['>', '|', '?'])
+        && underline.len() >= 2
+        && (underline.chars().all(|c| c == '-') || underline.chars().all(|c| c == '='))
+}
+
+fn parse_node(header: Line, body: &[Line], errors: &mut Vec<StoryParseError>) -> StoryNode {
+    let name = header.text;
+    if let Some(space) = name.find(char::is_whitespace) {
+        errors.push(header.error_at(
+            &name[space..],
+            format!("node name `{}` contains spaces", name),
+        ));
+    }
+    let mut parser = NodeParser {
+        blocks: body
+            .split(|line| line.text.is_empty())
+            .filter(|block| !block.is_empty())
+            .collect(),
+        index: 0,
+        elements: Vec::new(),
+        options: Vec::new(),
+        condition: None,
+    };
+    while parser.index < parser.blocks.len() {
+        if let Err(error) = parser.parse_block() {
+            errors.push(error);
+        }
+    }
+    if let Some((_, line)) = parser.condition.take() {
+        errors.push(line.error(String::from("expected a paragraph after the condition")));
+    }
+    parser.end_choice();
+    StoryNode::new(name.to_string(), parser.elements)
+}
+
+struct NodeParser<'a> {
+    blocks: Vec<&'a [Line<'a>]>,
+    // The next block to parse.
+    index: usize,
+    elements: Vec<StoryElement>,
+    // Options of the choice being parsed.
+    options: Vec<StoryOption>,
+    // A `?` condition waiting for its paragraph.
+    condition: Option<(StoryCondition, Line<'a>)>,
+}
+
+impl<'a> NodeParser<'a> {
+    fn parse_block(&mut self) -> ParseResult<()> {
+        let block = self.blocks[self.index];
+        self.index += 1;
+        let first = block[0];
+        if !first.text.starts_with('>') {
+            self.end_choice();
+        }
+        if first.text.starts_with(['>', '?']) {
+            if let Some((_, line)) = self.condition.take() {
+                return Err(line.error(String::from("expected a paragraph after the condition")));
+            }
+        }
+        if let Some(rest) = first.text.strip_prefix('?') {
+            let mut words = Words::new(first, rest);
+            let kind = words.expect("a condition after `?`")?;
+            let condition = parse_condition(kind, &mut words)?;
+            self.condition = Some((condition, first));
+            if block.len() > 1 {
+                let text = parse_paragraph(&block[1..])?;
+                self.push(StoryElement::Text(text));
+            }
+        } else if first.text.starts_with('>') {
+            let content = block
+                .iter()
+                .map(|line| match line.text.strip_prefix('>') {
+                    Some(text) => Ok(Line {
+                        text: text.trim(),
+                        ..*line
+                    }),
+                    None => Err(line.error(String::from("expected `>` to continue the block"))),
+                })
+                .collect::<ParseResult<Vec<Line>>>()?;
+            self.parse_directive_block(&content)?;
+        } else if let Some(header) = first.text.strip_prefix('|') {
+            self.parse_special_block(first, header, &block[1..])?;
+        } else {
+            let text = parse_paragraph(block)?;
+            self.push(StoryElement::Text(text));
+        }
+        Ok(())
+    }
+
+    // Adds element, under the waiting condition if there is one.
+    fn push(&mut self, element: StoryElement) {
+        match self.condition.take() {
+            Some((condition, _)) => self
+                .elements
+                .push(StoryElement::If(condition, Box::new(element))),
+            None => self.elements.push(element),
+        }
+    }
+
+    fn end_choice(&mut self) {
+        if !self.options.is_empty() {
+            let options = self.options.split_off(0);
+            self.elements
+                .push(StoryElement::Choice(StoryChoice::new(options)));
+        }
+    }
+
+    // `| SwitchCheck Skill` and `| Ask path` blocks, with the lines after the
+    // header in rest.
+    fn parse_special_block(
+        &mut self,
+        first: Line<'a>,
+        header: &'a str,
+        rest: &'a [Line<'a>],
+    ) -> ParseResult<()> {
+        let mut words = Words::new(first, header);
+        let kind = words.expect("`SwitchCheck`, `Ask` or `AskNumber` after `|`")?;
+        match kind {
+            "SwitchCheck" => {
+                let skill = words.expect("a skill after `SwitchCheck`")?;
+                words.end()?;
+                let mut cases = Vec::new();
+                if !rest.is_empty() {
+                    cases.push(parse_case(rest)?);
+                }
+                while let Some(next) = self.blocks.get(self.index) {
+                    if !starts_case(next[0].text) {
+                        break;
+                    }
+                    self.index += 1;
+                    cases.push(parse_case(next)?);
+                }
+                if cases.is_empty() {
+                    return Err(first.error_after(
+                        skill,
+                        String::from("expected `10. Text` cases after `SwitchCheck`"),
+                    ));
+                }
+                self.push(StoryElement::Switch(StorySwitch::new(
+                    skill.to_string(),
+                    cases,
+                )));
+            }
+            "Ask" | "AskNumber" => {
+                let kind = if kind == "AskNumber" {
+                    AskKind::Number {
+                        min: words.number("a minimum after `AskNumber`")?,
+                        max: words.number("a maximum after the minimum")?,
+                    }
+                } else {
+                    AskKind::Text
+                };
+                let path = words.expect("a path to store the answer in")?;
+                words.end()?;
+                let question = if !rest.is_empty() {
+                    rest
+                } else {
+                    match self.blocks.get(self.index) {
+                        Some(next) if !next[0].text.starts_with(['>', '|', '?']) => {
+                            self.index += 1;
+                            *next
+                        }
+                        _ => {
+                            return Err(first.error_after(
+                                path,
+                                String::from("expected the question after the path"),
+                            ))
+                        }
+                    }
+                };
+                let question = parse_paragraph(question)?.text;
+                let target = AccessPath::from(path.to_string());
+                self.push(StoryElement::Ask(StoryAsk::new(question, kind, target)));
+            }
+            _ => {
+                return Err(first.error_at(
+                    kind,
+                    format!(
+                        "unknown block `{}`, expected `SwitchCheck`, `Ask` or `AskNumber`",
+                        kind
+                    ),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    // Lines of a `>` block, without the `>`. Options are collected until
+    // something else ends the choice.
+    fn parse_directive_block(&mut self, content: &[Line<'a>]) -> ParseResult<()> {
+        let mut index = 0;
+        while index < content.len() {
+            let line = content[index];
+            let option_condition = match line.text.strip_prefix('?') {
+                Some(rest) => {
+                    let mut words = Words::new(line, rest);
+                    let kind = words.expect("a condition after `?`")?;
+                    index += 1;
+                    Some((parse_condition(kind, &mut words)?, line))
+                }
+                None => None,
+            };
+            if content
+                .get(index)
+                .is_some_and(|line| starts_quote(line.text))
+            {
+                let intro_text = parse_quoted(content, &mut index)?;
+                let result_text = match content.get(index) {
+                    Some(line) if starts_quote(line.text) => {
+                        Some(parse_quoted(content, &mut index)?)
+                    }
+                    _ => None,
+                };
+                let directive = match content.get(index) {
+                    Some(line) if !line.text.starts_with('?') && !starts_quote(line.text) => {
+                        match parse_directive(content, &mut index)? {
+                            Some(directive) => Some(directive),
+                            None => return Err(line.error(String::from("options cannot `Exit`"))),
+                        }
+                    }
+                    _ => None,
+                };
+                let mut option = StoryOption::with_directive(intro_text, result_text, directive);
+                if let Some((condition, _)) = option_condition {
+                    option.set_condition(condition);
+                }
+                self.options.push(option);
+                continue;
+            }
+            if let Some((_, line)) = option_condition {
+                return Err(line.error_after(
+                    line.text,
+                    String::from("expected a quoted option after the condition"),
+                ));
+            }
+            self.end_choice();
+            let mut words = Words::new(line, line.text);
+            let directive_condition = match words.peek() {
+                Some(kind) if kind.ends_with('?') => {
+                    words.next += 1;
+                    let condition = parse_condition(&kind[..kind.len() - 1], &mut words)?;
+                    index += 1;
+                    if index >= content.len() {
+                        return Err(line.error_after(
+                            line.text,
+                            String::from("expected a directive after the condition"),
+                        ));
+                    }
+                    Some(condition)
+                }
+                _ => None,
+            };
+            let element = match parse_directive(content, &mut index)? {
+                Some(directive) => StoryElement::Directive(directive),
+                None => StoryElement::Exit,
+            };
+            self.elements.push(match directive_condition {
+                Some(condition) => StoryElement::If(condition, Box::new(element)),
+                None => element,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn starts_quote(text: &str) -> bool {
+    text.starts_with(['\'', '"'])
+}
+
+// Text between quotes, which may continue over several lines. It ends at the
+// first line ending with the opening quote, so quotes inside are fine.
+fn parse_quoted<'a>(content: &[Line<'a>], index: &mut usize) -> ParseResult<Template> {
+    let first = content[*index];
+    let quote = first
+        .text
+        .chars()
+        .next()
+        .expect("quoted lines are not empty");
+    let mut pieces = Vec::new();
+    let mut line = first;
+    let mut rest = &first.text[1..];
+    loop {
+        *index += 1;
+        if let Some(last) = rest.strip_suffix(quote) {
+            pieces.push((line, last.trim()));
+            break;
+        }
+        pieces.push((line, rest.trim()));
+        match content.get(*index) {
+            Some(next) => {
+                line = *next;
+                rest = next.text;
+            }
+            None => {
+                return Err(first.error(format!(
+                    "unclosed `{}`, expected a line ending with `{}`",
+                    quote, quote
+                )))
+            }
+        }
+    }
+    parse_template(&pieces)
+}
+
+// Returns None for `Exit`.
+fn parse_directive(content: &[Line], index: &mut usize) -> ParseResult<Option<Directive>> {
+    let line = content[*index];
+    *index += 1;
+    let mut words = Words::new(line, line.text);
+    let name = words.expect("a directive")?;
+    let directive = match name {
+        "Goto" => {
+            words.arrow("`Goto`")?;
+            let node = words.expect("a node name after `->`")?;
+            Directive::Goto(node.to_string())
+        }
+        "Check" => {
+            let skill = words.expect("a skill after `Check`")?;
+            let threshold = words.number("a threshold after the skill")?;
+            let xp = match words.peek() {
+                Some(xp) if xp.ends_with("xp") => {
+                    words.next += 1;
+                    let amount = &xp[..xp.len() - 2];
+                    let amount = amount.parse::<i64>().map_err(|_| {
+                        line.error_at(xp, format!("expected xp like `20xp`, found `{}`", xp))
+                    })?;
+                    words.arrow("Check xp")?;
+                    amount
+                }
+                _ => {
+                    words.arrow("Check threshold")?;
+                    0
+                }
+            };
+            Directive::Check(StoryCheck {
+                skill: skill.to_string(),
+                threshold,
+                xp,
+                success_node: words.expect("a success node after `->`")?.to_string(),
+                failure_node: words
+                    .expect("a failure node after the success node")?
+                    .to_string(),
+            })
+        }
+        "Combat" => {
+            match words.peek() {
+                Some("{") => words.next += 1,
+                Some(word) => {
+                    return Err(line.error_at(word, String::from("expected `{` after `Combat`")))
+                }
+                None => return Err(words.missing(String::from("expected `{` after `Combat`"))),
+            }
+            words.end()?;
+            parse_combat(line, content, index)?
+        }
+        "AddItem" => Directive::AddItem(words.expect("an item id after `AddItem`")?.to_string()),
+        "Mark" => Directive::Mark(words.expect("a marker name after `Mark`")?.to_string()),
+        "Call" => Directive::Call(words.expect("a node name after `Call`")?.to_string()),
+        "Exit" => {
+            words.end()?;
+            return Ok(None);
+        }
+        _ => {
+            return Err(line.error_at(
+                name,
+                format!(
+                    "unknown directive `{}`, expected `Goto`, `Check`, `Combat`, `AddItem`, \
+                     `Mark`, `Call` or `Exit`",
+                    name
+                ),
+            ))
+        }
+    };
+    words.end()?;
+    Ok(Some(directive))
+}
+
+// The lines after `Combat {`: one `1x Enemy` per line, then
+// `} -> Victory Defeat`.
+fn parse_combat(opening: Line, content: &[Line], index: &mut usize) -> ParseResult<Directive> {
+    let mut enemies = Vec::new();
+    loop {
+        let line = match content.get(*index) {
+            Some(line) => *line,
+            None => {
+                return Err(opening.error(String::from(
+                    "unclosed `Combat {`, expected `} -> Victory Defeat`",
+                )))
+            }
+        };
+        *index += 1;
+        let mut words = Words::new(line, line.text);
+        let first = words.expect("`1x Enemy` or `} -> Victory Defeat`")?;
+        if first == "}" {
+            words.arrow("`}`")?;
+            let victory_node = words.expect("a victory node after `->`")?;
+            let defeat_node = words.expect("a defeat node after the victory node")?;
+            words.end()?;
+            return Ok(Directive::Combat(StoryCombat {
+                enemies,
+                victory_node: victory_node.to_string(),
+                defeat_node: defeat_node.to_string(),
+            }));
+        }
+        let count = first
+            .strip_suffix('x')
+            .and_then(|count| count.parse::<u32>().ok())
+            .ok_or_else(|| {
+                line.error_at(
+                    first,
+                    format!("expected a count like `2x`, found `{}`", first),
+                )
+            })?;
+        let enemy = words.expect("an enemy id after the count")?;
+        words.end()?;
+        enemies.push((count, enemy.to_string()));
+    }
+}
+
+fn parse_condition(kind: &str, words: &mut Words) -> ParseResult<StoryCondition> {
+    let condition = match kind {
+        "Marker" => {
+            let name = words.expect("a marker name after `Marker`")?;
+            match name.strip_prefix('!') {
+                Some(name) => StoryCondition::Marker {
+                    name: name.to_string(),
+                    negated: true,
+                },
+                None => StoryCondition::Marker {
+                    name: name.to_string(),
+                    negated: false,
+                },
+            }
+        }
+        "PassiveCheck" => StoryCondition::PassiveCheck {
+            skill: words.expect("a skill after `PassiveCheck`")?.to_string(),
+            threshold: words.number("a threshold after the skill")?,
+        },
+        _ => {
+            return Err(words.line.error_at(
+                kind,
+                format!(
+                    "unknown condition `{}`, expected `Marker` or `PassiveCheck`",
+                    kind
+                ),
+            ))
+        }
+    };
+    words.end()?;
+    Ok(condition)
+}
+
+// Lines joined with spaces; writers wrap paragraphs however they like.
+fn parse_paragraph(block: &[Line]) -> ParseResult<StoryText> {
+    let pieces: Vec<(Line, &str)> = block.iter().map(|line| (*line, line.text)).collect();
+    Ok(StoryText::new(parse_template(&pieces)?))
+}
+
+fn starts_case(text: &str) -> bool {
+    match text.find(". ") {
+        Some(dot) => dot > 0 && text[..dot].chars().all(|c| c.is_ascii_digit()),
+        None => false,
+    }
+}
+
+fn parse_case(block: &[Line]) -> ParseResult<SwitchCase> {
+    let first = block[0];
+    let dot = match first.text.find(". ") {
+        Some(dot) if starts_case(first.text) => dot,
+        _ => return Err(first.error(String::from("expected a case like `10. Text`"))),
+    };
+    let threshold = first.text[..dot]
+        .parse::<i64>()
+        .map_err(|_| first.error(String::from("expected a threshold")))?;
+    let mut pieces = vec![(first, first.text[dot + 2..].trim())];
+    pieces.extend(block[1..].iter().map(|line| (*line, line.text)));
+    Ok(SwitchCase {
+        threshold,
+        text: parse_template(&pieces)?,
+    })
+}
+
+// Parses pieces of lines joined with spaces, placing template errors back
+// on the line they came from.
+fn parse_template(pieces: &[(Line, &str)]) -> ParseResult<Template> {
+    let text: Vec<&str> = pieces.iter().map(|(_, piece)| *piece).collect();
+    Template::parse(&text.join(" ")).map_err(|error| {
+        let mut start = 0;
+        for (line, piece) in pieces {
+            if error.offset <= start + piece.len() {
+                return line.error_at(&piece[error.offset - start..], error.message);
+            }
+            start += piece.len() + 1;
+        }
+        let (line, piece) = pieces[pieces.len() - 1];
+        line.error_after(piece, error.message)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::story_graph::StoryGraph;
+
+    const EXAMPLE: &str = include_str!("../../examples/engine_v1_base.md");
+
+    #[test]
+    fn parses_the_example() {
+        let graph = StoryGraph::parse(EXAMPLE).unwrap();
+        let names: Vec<&str> = graph.nodes().map(|node| node.name()).collect();
+        assert_eq!(names[0], "BladeMountainAwakening");
+        assert_eq!(names.len(), 10);
+        assert_eq!(graph.start_node(), Some("BladeMountainAwakening"));
+        assert_eq!(graph.metadata().version.as_deref(), Some("1"));
+        let summit = graph.get_node("BladeMountainSummit");
+        assert_eq!(summit.elements().len(), 2);
+        match &summit.elements()[1] {
+            StoryElement::Choice(choice) => {
+                assert_eq!(choice.options.len(), 3);
+                assert_eq!(
+                    choice.options[1].directive,
+                    Some(Directive::Check(StoryCheck {
+                        skill: String::from("Perception"),
+                        threshold: 9,
+                        xp: 10,
+                        success_node: String::from("BladeMountainNote"),
+                        failure_node: String::from("BladeMountainFailedSearch"),
+                    }))
+                );
+            }
+            _ => panic!("expected a choice"),
+        }
+    }
+
+    #[test]
+    fn parses_headers() {
+        let (metadata, nodes) = parse_story(
+            "| Title  The Blade Mountain\n\
+             | Author A. Writer\n\
+             | Version 1.2\n\
+             | Start Top\n\n\
+             Top\n----\n\n> Exit\n",
+        )
+        .unwrap();
+        assert_eq!(
+            metadata,
+            StoryMetadata {
+                title: Some(String::from("The Blade Mountain")),
+                author: Some(String::from("A. Writer")),
+                version: Some(String::from("1.2")),
+                start_node: Some(String::from("Top")),
+            }
+        );
+        assert_eq!(nodes.len(), 1);
+        let errors = parse_errors(
+            "| Title One\n| Title Two\n| Start Top Bottom\n| Edition 3\n| Version\n\n\
+             Top\n----\n\n> Exit\n",
+        );
+        let found: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            found,
+            vec![
+                "line 2, column 3: `Title` is given twice",
+                "line 3, column 13: unexpected `Bottom`",
+                "line 4, column 3: unknown header key `Edition`, expected `Title`, `Author`, \
+                 `Version` or `Start`",
+                "line 5, column 10: expected a value after `Version`",
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_multi_line_options_and_conditions() {
+        let (_, nodes) = parse_story(
+            "Note\n----\n\n\
+             > 'Read the note'\n\
+             > 'It mentions the \"Gerr Empire,\" and you\n\
+             > should not be here.'\n\
+             > Goto -> Note\n\n\
+             > ?PassiveCheck Smell 15\n\
+             > 'Sniff.'\n\n\
+             ? Marker !MetStan\n\n\
+             Who is Stan?\n\n\
+             > Marker? MetStan\n\
+             > Goto -> Cabin\n",
+        )
+        .unwrap();
+        let elements = nodes[0].elements();
+        assert_eq!(elements.len(), 3);
+        match &elements[0] {
+            StoryElement::Choice(choice) => {
+                let result = choice.options[0].result_text.as_ref().unwrap();
+                assert_eq!(
+                    result.to_string(),
+                    "It mentions the \"Gerr Empire,\" and you should not be here."
+                );
+                assert_eq!(
+                    choice.options[1].condition,
+                    Some(StoryCondition::PassiveCheck {
+                        skill: String::from("Smell"),
+                        threshold: 15,
+                    })
+                );
+            }
+            _ => panic!("expected a choice"),
+        }
+        assert!(matches!(
+            &elements[1],
+            StoryElement::If(StoryCondition::Marker { negated: true, .. }, _)
+        ));
+        assert!(matches!(
+            &elements[2],
+            StoryElement::If(StoryCondition::Marker { negated: false, .. }, _)
+        ));
+    }
+
+    fn parse_errors(source: &str) -> Vec<StoryParseError> {
+        match parse_story(source) {
+            Ok(_) => panic!("expected errors"),
+            Err(errors) => errors,
+        }
+    }
+
+    #[test]
+    fn locates_errors() {
+        let errors = parse_errors("Top\n----\n\n> Check Sneak 15 Hidden Caught\n");
+        assert_eq!(
+            errors,
+            vec![StoryParseError {
+                line: 4,
+                column: 18,
+                message: String::from("expected `->` after Check threshold"),
+                source_line: String::from("> Check Sneak 15 Hidden Caught"),
+            }]
+        );
+        assert_eq!(
+            errors[0].render("story.md"),
+            "story.md:4:18: expected `->` after Check threshold\n  \
+             |\n\
+             4 | > Check Sneak 15 Hidden Caught\n  \
+             |                  ^\n"
+        );
+        assert_eq!(
+            parse_errors("Top\n----\n\n> Goto ->\n")[0].to_string(),
+            "line 4, column 10: expected a node name after `->`"
+        );
+        assert_eq!(
+            parse_errors("Top\n----\n\n>  Teleport -> Moon\n")[0].to_string(),
+            "line 4, column 4: unknown directive `Teleport`, expected `Goto`, `Check`, \
+             `Combat`, `AddItem`, `Mark`, `Call` or `Exit`"
+        );
+        assert_eq!(parse_errors("Just some text.\n\nTop\n----\n")[0].line, 1);
+        assert_eq!(parse_errors("Top\n----\n\n> 'Never closed\n")[0].column, 3);
+    }
+
+    #[test]
+    fn locates_template_errors_on_later_lines() {
+        let errors = parse_errors("Top\n----\n\nHello there,\nmy {friend.\n");
+        assert_eq!((errors[0].line, errors[0].column), (5, 4));
+        assert_eq!(errors[0].message, "unclosed `{`");
+    }
+
+    #[test]
+    fn reports_several_errors() {
+        let errors = parse_errors(
+            "Top\n----\n\n\
+             > Check Sneak high -> A B\n\n\
+             Fine text.\n\n\
+             > Mark\n\n\
+             Bottom Node\n----\n\n\
+             > Combat {\n> two Wolves\n> } -> Won Lost\n",
+        );
+        let found: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            found,
+            vec![
+                "line 4, column 15: expected a threshold after the skill, found `high`",
+                "line 8, column 7: expected a marker name after `Mark`",
+                "line 10, column 7: node name `Bottom Node` contains spaces",
+                "line 14, column 3: expected a count like `2x`, found `two`",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_duplicate_nodes() {
+        let errors = parse_errors(
+            "Top\n----\n\n\
+             > Goto -> Top\n\n\
+             Top\n----\n\n\
+             > Exit\n",
+        );
+        let found: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            found,
+            vec!["line 6, column 1: node `Top` is already defined on line 1"]
+        );
+    }
+}
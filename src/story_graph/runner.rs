@@ -0,0 +1,1612 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::accessible::Accessible;
+use crate::choice::Choice;
+use crate::command::{Command, CommandResponse};
+use crate::inventory::Item;
+use crate::io::{Interface, Interrupt};
+use crate::json::Json;
+use crate::stat::{ProgressCheck, StatKind};
+use crate::story_graph::coverage::Coverage;
+use crate::story_graph::save::{SaveError, SaveGame};
+use crate::story_graph::{skill_value, AskKind, Directive, StoryChoice, StoryElement, StoryGraph};
+use crate::template::{RenderError, Template};
+use crate::world::World;
+
+// Where the runner is in the story: the next element to run.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Cursor {
+    pub node: String,
+    pub element: usize,
+}
+
+impl Cursor {
+    pub fn start_of(node: &str) -> Cursor {
+        Cursor {
+            node: node.to_string(),
+            element: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PromptOption {
+    pub id: usize,
+    pub text: String,
+}
+
+impl Choice for PromptOption {
+    fn describe(&self) -> String {
+        self.text.clone()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StoryError {
+    MissingNode(String),
+    // The node ran out of elements without reaching a choice or an exit.
+    FellOffNode(String),
+    // A question's target path does not accept answers.
+    CannotAssign(String),
+    // Node went round and round without showing anything.
+    EndlessLoop(String),
+    // A directive the runner cannot run yet.
+    Unsupported(String),
+    // Calls went more than CALL_DEPTH_LIMIT deep, last into this node.
+    CallTooDeep(String),
+    // Text in the node could not be rendered in strict mode.
+    Render(String, RenderError),
+}
+
+impl fmt::Display for StoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoryError::MissingNode(node) => write!(f, "no story node named `{}`", node),
+            StoryError::FellOffNode(node) => {
+                write!(f, "story node `{}` ended without a choice or exit", node)
+            }
+            StoryError::CannotAssign(path) => write!(f, "answers cannot be stored in `{}`", path),
+            StoryError::EndlessLoop(node) => {
+                write!(f, "story node `{}` loops without showing anything", node)
+            }
+            StoryError::Unsupported(directive) => {
+                write!(f, "`{}` is not supported yet", directive)
+            }
+            StoryError::CallTooDeep(node) => {
+                write!(f, "calls went too deep calling story node `{}`", node)
+            }
+            StoryError::Render(node, error) => {
+                write!(f, "story node `{}` cannot be shown: {}", node, error)
+            }
+        }
+    }
+}
+
+impl Error for StoryError {}
+
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    Output(String),
+    // The runner waits for answer() before it can go further. Stepping again
+    // repeats the prompt.
+    Prompt(Vec<PromptOption>),
+    // Like a prompt, but answered with answer_text().
+    Ask(Question),
+    Finished,
+    // The story is broken at this point; the runner is finished.
+    Error(StoryError),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Question {
+    pub text: String,
+    pub kind: AskKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AnswerError {
+    NoPendingPrompt,
+    UnknownOption(usize),
+    NoPendingQuestion,
+    EmptyAnswer,
+    NumberOutOfRange { min: i64, max: i64 },
+}
+
+impl fmt::Display for AnswerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnswerError::NoPendingPrompt => write!(f, "there is no choice to answer"),
+            AnswerError::UnknownOption(id) => write!(f, "no option with id {}", id),
+            AnswerError::NoPendingQuestion => write!(f, "there is no question to answer"),
+            AnswerError::EmptyAnswer => write!(f, "the answer is empty"),
+            AnswerError::NumberOutOfRange { min, max } => {
+                write!(f, "the answer must be a number from {} to {}", min, max)
+            }
+        }
+    }
+}
+
+impl Error for AnswerError {}
+
+// How many silent elements, such as directives, one step runs through before
+// giving up on an endless loop.
+const SILENT_STEP_LIMIT: usize = 1000;
+
+// How many calls can be under way at once, to catch nodes that call
+// themselves forever.
+const CALL_DEPTH_LIMIT: usize = 100;
+
+// How many choices back undo and rewind can go by default.
+pub const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+// The world and cursor as they were when a choice was first offered.
+#[derive(Clone)]
+struct Snapshot {
+    world: World,
+    cursor: Cursor,
+    calls: Vec<Cursor>,
+}
+
+// Runs a story one step at a time. Frontends alternate step() and, when
+// prompted, answer(), so the runner never blocks on input. Cloning a runner
+// forks the playthrough.
+#[derive(Clone)]
+pub struct StoryRunner<'g> {
+    graph: &'g StoryGraph,
+    world: World,
+    // None once the story has finished.
+    cursor: Option<Cursor>,
+    // Where each call under way returns to, innermost last.
+    calls: Vec<Cursor>,
+    // Output produced by answer(), returned by the following steps.
+    queued: VecDeque<String>,
+    prompt: Option<Vec<PromptOption>>,
+    question: Option<Question>,
+    // Reported by the next step, after any queued output.
+    error: Option<StoryError>,
+    // Oldest first. The newest snapshot is of the pending choice, if any.
+    history: VecDeque<Snapshot>,
+    history_limit: usize,
+    // Set while the pending prompt picks a snapshot to rewind to, rather
+    // than an option of the story: the prompt and question it replaced,
+    // which staying puts back.
+    rewinding: Option<(Option<Vec<PromptOption>>, Option<Question>)>,
+    coverage: Coverage,
+    // Where the save and load commands keep the game. Without a save file,
+    // it is kept in saved and lasts only as long as the runner.
+    save_file: Option<PathBuf>,
+    saved: Option<SaveGame>,
+}
+
+impl<'g> StoryRunner<'g> {
+    // Starts at the story's start node, after its title screen.
+    pub fn start(graph: &'g StoryGraph, world: World) -> StoryRunner<'g> {
+        let mut runner = StoryRunner::new(graph, world, graph.start_node().unwrap_or_default());
+        if graph.start_node().is_none() {
+            runner.cursor = None;
+        }
+        runner.queued.extend(graph.metadata().title_screen());
+        runner
+    }
+
+    pub fn new(graph: &'g StoryGraph, world: World, start_node: &str) -> StoryRunner<'g> {
+        StoryRunner {
+            graph,
+            world,
+            cursor: Some(Cursor::start_of(start_node)),
+            calls: Vec::new(),
+            queued: VecDeque::new(),
+            prompt: None,
+            question: None,
+            error: None,
+            history: VecDeque::new(),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            rewinding: None,
+            coverage: Coverage::default(),
+            save_file: None,
+            saved: None,
+        }
+    }
+
+    // Makes the save and load commands write and read path.
+    pub fn set_save_file(&mut self, path: PathBuf) {
+        self.save_file = Some(path);
+    }
+
+    // Older snapshots are dropped once there are more than limit.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+        self.trim_history();
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    // For frontends that change the world outside the story, e.g. combat.
+    #[allow(dead_code)]
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    // What this playthrough has visited so far.
+    pub fn coverage(&self) -> &Coverage {
+        &self.coverage
+    }
+
+    pub fn cursor(&self) -> Option<&Cursor> {
+        self.cursor.as_ref()
+    }
+
+    // Where the calls under way return to, innermost last.
+    pub fn calls(&self) -> &[Cursor] {
+        &self.calls
+    }
+
+    // The playthrough so far, or None once the story has finished.
+    pub fn save(&self) -> Option<SaveGame> {
+        Some(SaveGame {
+            story_version: self.graph.metadata().version.clone(),
+            cursor: self.cursor.clone()?,
+            calls: self.calls.clone(),
+            world: self.world.clone(),
+        })
+    }
+
+    // Picks up a saved playthrough where it left off, migrating saves from
+    // earlier versions of the story. The player is told if the saved place
+    // is gone and the story resumes somewhere safe instead.
+    pub fn load(&mut self, mut save: SaveGame) -> Result<(), SaveError> {
+        let lost = save.migrate(self.graph)?;
+        self.stop();
+        self.error = None;
+        self.history.clear();
+        if let Some(lost) = lost {
+            self.queued.push_back(format!(
+                "[[The story has changed since you saved, and `{}` is gone. You continue from `{}`.]]",
+                lost, save.cursor.node
+            ));
+        }
+        self.world = save.world;
+        self.cursor = Some(save.cursor);
+        self.calls = save.calls;
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub fn is_finished(&self) -> bool {
+        self.cursor.is_none() && self.queued.is_empty() && self.error.is_none()
+    }
+
+    pub fn step(&mut self) -> Step {
+        if let Some(output) = self.queued.pop_front() {
+            return Step::Output(output);
+        }
+        if let Some(error) = self.error.take() {
+            return Step::Error(error);
+        }
+        if let Some(prompt) = &self.prompt {
+            return Step::Prompt(prompt.clone());
+        }
+        if let Some(question) = &self.question {
+            return Step::Ask(question.clone());
+        }
+        // Directives and skipped elements show nothing, so the runner carries
+        // on until something does.
+        for _ in 0..SILENT_STEP_LIMIT {
+            let cursor = match &self.cursor {
+                Some(cursor) => cursor.clone(),
+                None => return Step::Finished,
+            };
+            let node = match self.graph.find_node(&cursor.node) {
+                Some(node) => node,
+                None => return self.fail(StoryError::MissingNode(cursor.node)),
+            };
+            let mut element = match node.elements.get(cursor.element) {
+                Some(element) => element,
+                None => match self.calls.pop() {
+                    // The called node is done.
+                    Some(caller) => {
+                        self.cursor = Some(caller);
+                        continue;
+                    }
+                    None => return self.fail(StoryError::FellOffNode(cursor.node)),
+                },
+            };
+            if cursor.element == 0 {
+                self.coverage.record_node(&cursor.node);
+            }
+            self.coverage.record_element(&cursor.node, cursor.element);
+            if let StoryElement::If(condition, conditional) = element {
+                if !condition.holds(&self.world) {
+                    self.advance();
+                    continue;
+                }
+                element = conditional;
+            }
+            match element {
+                StoryElement::Text(text) => {
+                    self.advance();
+                    return match self.render(&text.text) {
+                        Ok(output) => Step::Output(output),
+                        Err(error) => self.fail(error),
+                    };
+                }
+                StoryElement::Choice(choice) => {
+                    self.history.push_back(Snapshot {
+                        world: self.world.clone(),
+                        cursor,
+                        calls: self.calls.clone(),
+                    });
+                    self.trim_history();
+                    let prompt = match self.render_prompt(choice) {
+                        Ok(prompt) => prompt,
+                        Err(error) => return self.fail(error),
+                    };
+                    self.prompt = Some(prompt.clone());
+                    return Step::Prompt(prompt);
+                }
+                StoryElement::Ask(ask) => {
+                    let question = Question {
+                        text: match self.render(&ask.question) {
+                            Ok(text) => text,
+                            Err(error) => return self.fail(error),
+                        },
+                        kind: ask.kind,
+                    };
+                    self.question = Some(question.clone());
+                    return Step::Ask(question);
+                }
+                StoryElement::Switch(switch) => {
+                    self.advance();
+                    if let Some(case) = switch.case_for(&self.world) {
+                        return match self.render(&case.text) {
+                            Ok(output) => Step::Output(output),
+                            Err(error) => self.fail(error),
+                        };
+                    }
+                }
+                StoryElement::Directive(directive) => {
+                    if let Err(error) = self.run_directive(directive) {
+                        return self.fail(error);
+                    }
+                }
+                StoryElement::If(_, _) => {
+                    unreachable!("conditional elements are not nested")
+                }
+                StoryElement::Exit => {
+                    self.cursor = None;
+                    return Step::Output(String::from("[[Goodbye! Thanks for playing.]]"));
+                }
+            }
+        }
+        let node = self.cursor.as_ref().map(|cursor| cursor.node.clone());
+        self.fail(StoryError::EndlessLoop(node.unwrap_or_default()))
+    }
+
+    pub fn answer(&mut self, option_id: usize) -> Result<(), AnswerError> {
+        let prompt = self.prompt.as_ref().ok_or(AnswerError::NoPendingPrompt)?;
+        if !prompt.iter().any(|option| option.id == option_id) {
+            return Err(AnswerError::UnknownOption(option_id));
+        }
+        self.prompt = None;
+        if let Some((prompt, question)) = self.rewinding.take() {
+            // The last option stays at the current choice.
+            if option_id < self.earlier_snapshots() {
+                self.restore(option_id);
+            } else {
+                self.prompt = prompt;
+                self.question = question;
+            }
+            return Ok(());
+        }
+        let choice = match self.current_element() {
+            Some(StoryElement::Choice(choice)) => choice,
+            _ => unreachable!("prompts are only pending at choices"),
+        };
+        if let Some(cursor) = &self.cursor {
+            self.coverage
+                .record_option(&cursor.node, cursor.element, option_id);
+        }
+        let option = &choice.options[option_id];
+        if let Some(result_text) = &option.result_text {
+            match self.render(result_text) {
+                Ok(result) => self.queued.push_back(result),
+                Err(error) => {
+                    self.stop();
+                    self.error = Some(error);
+                    return Ok(());
+                }
+            }
+        }
+        match &option.directive {
+            Some(directive) => {
+                if let Err(error) = self.run_directive(directive) {
+                    self.stop();
+                    self.error = Some(error);
+                }
+            }
+            None => self.advance(),
+        }
+        Ok(())
+    }
+
+    // Moves the cursor on from the directive, to another node if it goes to
+    // one.
+    fn run_directive(&mut self, directive: &Directive) -> Result<(), StoryError> {
+        match directive {
+            Directive::Goto(node) => self.cursor = Some(Cursor::start_of(node)),
+            Directive::Check(check) => {
+                let success = match StatKind::from_name(&check.skill) {
+                    Some(kind) => self.world.player.stats.check_with_progression(
+                        kind,
+                        ProgressCheck {
+                            required: check.threshold,
+                            base_progress: check.xp,
+                        },
+                    ),
+                    None => skill_value(&self.world, &check.skill) >= check.threshold,
+                };
+                let next_node = if success {
+                    &check.success_node
+                } else {
+                    &check.failure_node
+                };
+                self.cursor = Some(Cursor::start_of(next_node));
+            }
+            Directive::Combat(_) => return Err(StoryError::Unsupported(String::from("Combat"))),
+            Directive::AddItem(id) => {
+                // Items missing from the catalog are named by their id.
+                let item = match self.graph.item(id) {
+                    Some(item) => item.clone(),
+                    None => Item::new(id.clone(), id.clone(), Vec::new()),
+                };
+                self.world.player.inventory.add_item(item);
+                self.advance();
+            }
+            Directive::Mark(marker) => {
+                self.world.markers.set(marker);
+                self.advance();
+            }
+            Directive::Call(node) => {
+                if self.calls.len() >= CALL_DEPTH_LIMIT {
+                    return Err(StoryError::CallTooDeep(node.clone()));
+                }
+                self.advance();
+                if let Some(caller) = self.cursor.replace(Cursor::start_of(node)) {
+                    self.calls.push(caller);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Stores the answer to the pending question in the world. Numbers must
+    // be in the question's range.
+    pub fn answer_text(&mut self, answer: &str) -> Result<(), AnswerError> {
+        let question = self
+            .question
+            .as_ref()
+            .ok_or(AnswerError::NoPendingQuestion)?;
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Err(AnswerError::EmptyAnswer);
+        }
+        if let AskKind::Number { min, max } = question.kind {
+            match answer.parse::<i64>() {
+                Ok(number) if number >= min && number <= max => {}
+                _ => return Err(AnswerError::NumberOutOfRange { min, max }),
+            }
+        }
+        self.question = None;
+        let ask = match self.current_element() {
+            Some(StoryElement::Ask(ask)) => ask,
+            _ => unreachable!("questions are only pending at asks"),
+        };
+        if self.world.assign(ask.target.view(), answer) {
+            self.advance();
+        } else {
+            self.stop();
+            self.error = Some(StoryError::CannotAssign(ask.target.to_string()));
+        }
+        Ok(())
+    }
+
+    // Answers a meta-command through the graph's command hook. A stop
+    // response finishes the story.
+    pub fn command(&mut self, command: Command) -> CommandResponse {
+        match command {
+            Command::Undo => return self.undo(),
+            Command::Rewind => return self.offer_rewind(),
+            Command::Save => return self.save_game(),
+            Command::Load => return self.load_game(),
+            _ => {}
+        }
+        let response = self.graph.respond_to_command(command, &self.world);
+        if let CommandResponse::Stop(_) = response {
+            self.stop();
+        }
+        response
+    }
+
+    fn save_game(&mut self) -> CommandResponse {
+        let save = match self.save() {
+            Some(save) => save,
+            None => {
+                return CommandResponse::Continue(String::from("[[There is nothing to save.]]"))
+            }
+        };
+        let path = match &self.save_file {
+            Some(path) => path,
+            None => {
+                self.saved = Some(save);
+                return CommandResponse::Continue(String::from("[[Game saved.]]"));
+            }
+        };
+        match fs::write(path, format!("{}\n", save.to_json())) {
+            Ok(()) => CommandResponse::Continue(String::from("[[Game saved.]]")),
+            Err(error) => {
+                CommandResponse::Continue(format!("[[The game could not be saved: {}.]]", error))
+            }
+        }
+    }
+
+    fn load_game(&mut self) -> CommandResponse {
+        let saved = match &self.save_file {
+            Some(path) => match fs::read_to_string(path) {
+                Ok(text) => Json::parse(&text)
+                    .map_err(|error| SaveError::Malformed(error.to_string()))
+                    .and_then(|json| SaveGame::from_json(&json, self.graph)),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                    return CommandResponse::Continue(String::from("[[There is no saved game.]]"))
+                }
+                Err(error) => {
+                    return CommandResponse::Continue(format!(
+                        "[[The saved game could not be read: {}.]]",
+                        error
+                    ))
+                }
+            },
+            None => match &self.saved {
+                Some(save) => Ok(save.clone()),
+                None => {
+                    return CommandResponse::Continue(String::from("[[There is no saved game.]]"))
+                }
+            },
+        };
+        match saved.and_then(|save| self.load(save)) {
+            Ok(()) => CommandResponse::Continue(String::from("[[Game loaded.]]")),
+            Err(error) => CommandResponse::Continue(format!(
+                "[[The saved game cannot be loaded: {}.]]",
+                error
+            )),
+        }
+    }
+
+    // Takes back the last choice, returning to it with the world as it was
+    // then.
+    pub fn undo(&mut self) -> CommandResponse {
+        match self.earlier_snapshots() {
+            0 => CommandResponse::Continue(String::from("[[There is nothing to undo.]]")),
+            earlier => {
+                self.restore(earlier - 1);
+                CommandResponse::Continue(String::from("[[You take back your last choice.]]"))
+            }
+        }
+    }
+
+    // Returns to the most recent choice in node, as undo would. Returns
+    // whether node is in the history.
+    #[allow(dead_code)]
+    pub fn rewind_to(&mut self, node: &str) -> bool {
+        let earlier = self.earlier_snapshots();
+        match self
+            .history
+            .iter()
+            .take(earlier)
+            .rposition(|snapshot| snapshot.cursor.node == node)
+        {
+            Some(index) => {
+                self.restore(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Replaces the pending prompt with one listing earlier choices, newest
+    // first.
+    fn offer_rewind(&mut self) -> CommandResponse {
+        let earlier = self.earlier_snapshots();
+        if earlier == 0 {
+            return CommandResponse::Continue(String::from("[[There is nothing to rewind to.]]"));
+        }
+        let mut options: Vec<PromptOption> = self
+            .history
+            .iter()
+            .take(earlier)
+            .enumerate()
+            .rev()
+            .map(|(id, snapshot)| PromptOption {
+                id,
+                text: format!("Go back to {}.", snapshot.cursor.node),
+            })
+            .collect();
+        options.push(PromptOption {
+            id: earlier,
+            text: String::from("Stay here."),
+        });
+        let replaced = match self.rewinding.take() {
+            Some(replaced) => replaced,
+            None => (self.prompt.take(), self.question.take()),
+        };
+        self.prompt = Some(options);
+        self.rewinding = Some(replaced);
+        CommandResponse::Continue(String::from("[[Where do you want to go back to?]]"))
+    }
+
+    // Snapshots before the pending choice's own.
+    fn earlier_snapshots(&self) -> usize {
+        let at_choice = match (self.history.back(), &self.cursor) {
+            (Some(snapshot), Some(cursor)) => {
+                snapshot.cursor == *cursor && snapshot.calls == self.calls
+            }
+            _ => false,
+        };
+        if at_choice {
+            self.history.len() - 1
+        } else {
+            self.history.len()
+        }
+    }
+
+    // Returns to the snapshot at index, forgetting it and everything after.
+    // The choice is offered again, which snapshots it again.
+    fn restore(&mut self, index: usize) {
+        let snapshot = self
+            .history
+            .drain(index..)
+            .next()
+            .expect("index is in the history");
+        self.world = snapshot.world;
+        self.cursor = Some(snapshot.cursor);
+        self.calls = snapshot.calls;
+        self.queued.clear();
+        self.prompt = None;
+        self.question = None;
+        self.rewinding = None;
+    }
+
+    fn trim_history(&mut self) {
+        while self.history.len() > self.history_limit {
+            self.history.pop_front();
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.cursor = None;
+        self.calls.clear();
+        self.prompt = None;
+        self.question = None;
+        self.rewinding = None;
+        self.queued.clear();
+    }
+
+    // Looks inside conditional elements, which only stop the runner if
+    // their condition held.
+    fn current_element(&self) -> Option<&'g StoryElement> {
+        let cursor = self.cursor.as_ref()?;
+        match self
+            .graph
+            .find_node(&cursor.node)?
+            .elements
+            .get(cursor.element)?
+        {
+            StoryElement::If(_, conditional) => Some(conditional),
+            element => Some(element),
+        }
+    }
+
+    fn advance(&mut self) {
+        if let Some(cursor) = &mut self.cursor {
+            cursor.element += 1;
+        }
+    }
+
+    fn fail(&mut self, error: StoryError) -> Step {
+        self.stop();
+        Step::Error(error)
+    }
+
+    // Renders template against the world, in the graph's render mode.
+    fn render(&self, template: &Template) -> Result<String, StoryError> {
+        self.graph.render(template, &self.world).map_err(|error| {
+            let node = self.cursor.as_ref().map(|cursor| cursor.node.clone());
+            StoryError::Render(node.unwrap_or_default(), error)
+        })
+    }
+
+    // Options whose condition fails are left out, keeping the ids of the rest.
+    fn render_prompt(&self, choice: &StoryChoice) -> Result<Vec<PromptOption>, StoryError> {
+        choice
+            .options
+            .iter()
+            .enumerate()
+            .filter(|(_, option)| match &option.condition {
+                Some(condition) => condition.holds(&self.world),
+                None => true,
+            })
+            .map(|(id, option)| {
+                Ok(PromptOption {
+                    id,
+                    text: self.render(&option.intro_text)?,
+                })
+            })
+            .collect()
+    }
+}
+
+// Drives a runner with a blocking Interface until the story finishes, the
+// player quits, or input ends.
+pub fn run<I: Interface>(runner: &mut StoryRunner, interface: &mut I) -> Result<(), StoryError> {
+    loop {
+        let interrupt = match runner.step() {
+            Step::Output(output) => {
+                interface.write(&output);
+                continue;
+            }
+            Step::Prompt(options) => match interface.choose(options) {
+                Ok(chosen) => {
+                    runner
+                        .answer(chosen.id)
+                        .expect("interfaces choose from the options given");
+                    continue;
+                }
+                Err(interrupt) => interrupt,
+            },
+            Step::Ask(question) => {
+                let answer = match question.kind {
+                    AskKind::Text => interface.ask_text(&question.text),
+                    AskKind::Number { min, max } => interface
+                        .ask_number(&question.text, min, max)
+                        .map(|number| number.to_string()),
+                };
+                match answer {
+                    Ok(answer) => {
+                        // The question stays pending, so it is asked again.
+                        if let Err(error) = runner.answer_text(&answer) {
+                            interface.write(&format!("[[That will not do: {}.]]", error));
+                        }
+                        continue;
+                    }
+                    Err(interrupt) => interrupt,
+                }
+            }
+            Step::Finished => return Ok(()),
+            Step::Error(error) => return Err(error),
+        };
+        match interrupt {
+            Interrupt::Command(command) => match runner.command(command) {
+                CommandResponse::Continue(message) | CommandResponse::Stop(message) => {
+                    interface.write(&message)
+                }
+            },
+            Interrupt::EndOfInput => {
+                runner.stop();
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accessible::AccessPath;
+    use crate::io::{TestInput, TestInterface};
+    use crate::stat::StatKind;
+    use crate::story_graph::save::{Migration, Migrations};
+    use crate::story_graph::{StoryAsk, StoryNode, StoryOption, StoryText};
+    use crate::template;
+    use crate::template::{RenderMode, Template};
+
+    fn run_graph(graph: &StoryGraph, start_node: &str, interface: &mut TestInterface) {
+        let mut runner = StoryRunner::new(graph, World::empty(), start_node);
+        run(&mut runner, interface).unwrap();
+    }
+
+    fn foo_choice_graph() -> StoryGraph {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
+            "FooNode".to_string(),
+            vec![
+                StoryElement::Choice(StoryChoice {
+                    options: vec![StoryOption {
+                        intro_text: Template::raw_from_str("Foo\n"),
+                        result_text: Some(Template::raw_from_str("Chose Foo\n")),
+                        condition: None,
+                        directive: None,
+                    }],
+                }),
+                StoryElement::Exit,
+            ],
+        ));
+        graph
+    }
+
+    #[test]
+    pub fn text_nodes_write_text() {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
+            "FooNode".to_string(),
+            vec![
+                StoryElement::Text(StoryText {
+                    text: Template {
+                        tokens: vec![
+                            template::Token::Text(String::from("player.name: ")),
+                            template::Token::Accessor(AccessPath::from(String::from(
+                                "player.name",
+                            ))),
+                            template::Token::Text(String::from(".\n")),
+                        ],
+                    },
+                }),
+                StoryElement::Exit,
+            ],
+        ));
+        let mut interface = TestInterface::new(VecDeque::new());
+        run_graph(&graph, "FooNode", &mut interface);
+        assert_eq!(
+            interface.written,
+            "player.name: Player.\nGoodbye! Thanks for playing."
+        );
+    }
+
+    #[test]
+    pub fn strict_graphs_report_unresolved_paths() {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
+            "FooNode".to_string(),
+            vec![
+                StoryElement::Text(StoryText {
+                    text: Template {
+                        tokens: vec![template::Token::Accessor(AccessPath::from(String::from(
+                            "player.nmae",
+                        )))],
+                    },
+                }),
+                StoryElement::Exit,
+            ],
+        ));
+        graph.set_render_mode(RenderMode::Strict);
+        let mut interface = TestInterface::new(VecDeque::new());
+        let mut runner = StoryRunner::new(&graph, World::empty(), "FooNode");
+        let error = run(&mut runner, &mut interface).unwrap_err();
+        match &error {
+            StoryError::Render(node, render_error) => {
+                assert_eq!(node, "FooNode");
+                assert_eq!(render_error.unresolved[0].path.as_str(), "player.nmae");
+            }
+            error => panic!("expected a render error, got {:?}", error),
+        }
+        assert_eq!(
+            error.to_string(),
+            "story node `FooNode` cannot be shown: unresolved template paths:\n  \
+             {player.nmae} (token 0, offset 0)"
+        );
+        assert!(runner.is_finished());
+    }
+
+    #[test]
+    pub fn choices_without_a_next_node_run() {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
+            "FooNode".to_string(),
+            vec![
+                StoryElement::Choice(StoryChoice {
+                    options: vec![
+                        StoryOption {
+                            intro_text: Template::raw_from_str("Foo\n"),
+                            result_text: None,
+                            condition: None,
+                            directive: None,
+                        },
+                        StoryOption {
+                            intro_text: Template::raw_from_str("Bar\n"),
+                            result_text: Some(Template::raw_from_str("Baz\n")),
+                            condition: None,
+                            directive: None,
+                        },
+                    ],
+                }),
+                StoryElement::Exit,
+            ],
+        ));
+        let mut interface = TestInterface::new(VecDeque::from(vec![1, 0]));
+        run_graph(&graph, "FooNode", &mut interface);
+        assert_eq!(interface.written, "Baz\nGoodbye! Thanks for playing.");
+        interface.written.clear();
+        run_graph(&graph, "FooNode", &mut interface);
+        assert_eq!(interface.written, "Goodbye! Thanks for playing.");
+    }
+
+    #[test]
+    pub fn choices_stop_when_input_ends() {
+        let graph = foo_choice_graph();
+        let mut interface = TestInterface::new(VecDeque::new());
+        run_graph(&graph, "FooNode", &mut interface);
+        assert_eq!(interface.written, "");
+    }
+
+    #[test]
+    pub fn choices_handle_commands() {
+        let mut graph = foo_choice_graph();
+        graph.set_command_hook(Box::new(|command, world| match command {
+            Command::Help => Some(CommandResponse::Continue(format!(
+                "Help {}.\n",
+                world.player.name
+            ))),
+            _ => None,
+        }));
+        let mut interface = TestInterface::with_inputs(VecDeque::from(vec![
+            TestInput::Command(Command::Inventory),
+            TestInput::Command(Command::Help),
+            TestInput::Choice(0),
+        ]));
+        run_graph(&graph, "FooNode", &mut interface);
+        assert_eq!(
+            interface.written,
+            "You aren't carrying anything.Help Player.\nChose Foo\nGoodbye! Thanks for playing."
+        );
+        let mut interface =
+            TestInterface::with_inputs(VecDeque::from(vec![TestInput::Command(Command::Quit)]));
+        run_graph(&graph, "FooNode", &mut interface);
+        assert_eq!(interface.written, "Goodbye! Thanks for playing.");
+    }
+
+    #[test]
+    pub fn choices_with_a_next_node_run() {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
+            "FooNode".to_string(),
+            vec![
+                StoryElement::Text(StoryText {
+                    text: Template::raw_from_str("Choose Foo or Bar\n"),
+                }),
+                StoryElement::Choice(StoryChoice {
+                    options: vec![
+                        StoryOption {
+                            intro_text: Template::raw_from_str("Foo\n"),
+                            result_text: Some(Template::raw_from_str("Chose Foo\n")),
+                            condition: None,
+                            directive: Some(Directive::Goto(String::from("FooNode"))),
+                        },
+                        StoryOption {
+                            intro_text: Template::raw_from_str("Bar\n"),
+                            result_text: None,
+                            condition: None,
+                            directive: Some(Directive::Goto(String::from("BarNode"))),
+                        },
+                    ],
+                }),
+            ],
+        ));
+        graph.add_node(StoryNode::new(
+            "BarNode".to_string(),
+            vec![
+                StoryElement::Text(StoryText {
+                    text: Template::raw_from_str("Reached Bar\n"),
+                }),
+                StoryElement::Exit,
+            ],
+        ));
+        let mut interface = TestInterface::new(VecDeque::from(vec![0, 0, 0, 1]));
+        run_graph(&graph, "FooNode", &mut interface);
+        assert_eq!(
+            interface.written,
+            "Choose Foo or Bar
+Chose Foo
+Choose Foo or Bar
+Chose Foo
+Choose Foo or Bar
+Chose Foo
+Choose Foo or Bar
+Reached Bar
+Goodbye! Thanks for playing."
+        );
+    }
+
+    #[test]
+    pub fn steps_without_blocking() {
+        let graph = foo_choice_graph();
+        let mut runner = StoryRunner::new(&graph, World::empty(), "FooNode");
+        let prompt = vec![PromptOption {
+            id: 0,
+            text: String::from("Foo\n"),
+        }];
+        assert_eq!(runner.answer(0), Err(AnswerError::NoPendingPrompt));
+        assert_eq!(runner.step(), Step::Prompt(prompt.clone()));
+        // Stepping again repeats the pending prompt.
+        assert_eq!(runner.step(), Step::Prompt(prompt));
+        assert_eq!(runner.answer(3), Err(AnswerError::UnknownOption(3)));
+        assert_eq!(runner.answer(0), Ok(()));
+        assert_eq!(runner.step(), Step::Output(String::from("Chose Foo\n")));
+        assert_eq!(
+            runner.step(),
+            Step::Output(String::from("[[Goodbye! Thanks for playing.]]"))
+        );
+        assert!(runner.is_finished());
+        assert_eq!(runner.step(), Step::Finished);
+    }
+
+    #[test]
+    pub fn reports_broken_stories() {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
+            "FooNode".to_string(),
+            vec![StoryElement::Text(StoryText {
+                text: Template::raw_from_str("Foo"),
+            })],
+        ));
+        let mut runner = StoryRunner::new(&graph, World::empty(), "FooNode");
+        assert_eq!(runner.step(), Step::Output(String::from("Foo")));
+        assert_eq!(
+            runner.step(),
+            Step::Error(StoryError::FellOffNode(String::from("FooNode")))
+        );
+        assert_eq!(runner.step(), Step::Finished);
+        let mut runner = StoryRunner::new(&graph, World::empty(), "BarNode");
+        assert_eq!(
+            runner.step(),
+            Step::Error(StoryError::MissingNode(String::from("BarNode")))
+        );
+    }
+
+    fn question_graph(target: &str) -> StoryGraph {
+        let mut graph = StoryGraph::new();
+        graph.add_node(StoryNode::new(
+            "FooNode".to_string(),
+            vec![
+                StoryElement::Ask(StoryAsk::new(
+                    Template::raw_from_str("What is your name?"),
+                    AskKind::Text,
+                    AccessPath::from(String::from(target)),
+                )),
+                StoryElement::Ask(StoryAsk::new(
+                    Template::raw_from_str("How many coins?"),
+                    AskKind::Number { min: 1, max: 5 },
+                    AccessPath::from(String::from("vars.coins")),
+                )),
+                StoryElement::Text(StoryText::new(
+                    Template::parse("{player.name} has {vars.coins} coins.").unwrap(),
+                )),
+                StoryElement::Exit,
+            ],
+        ));
+        graph
+    }
+
+    #[test]
+    pub fn questions_store_answers_in_the_world() {
+        let graph = question_graph("player.name");
+        let mut interface = TestInterface::with_inputs(VecDeque::from(vec![
+            TestInput::Text(String::from("Ada")),
+            TestInput::Command(Command::Inventory),
+            TestInput::Text(String::from("9")),
+            TestInput::Text(String::from("3")),
+        ]));
+        run_graph(&graph, "FooNode", &mut interface);
+        assert_eq!(
+            interface.written,
+            "What is your name?How many coins?You aren't carrying anything.\
+             How many coins?Enter a number from 1 to 5.How many coins?\
+             Ada has 3 coins.Goodbye! Thanks for playing."
+        );
+    }
+
+    #[test]
+    pub fn asks_again_after_rejected_answers() {
+        let graph = question_graph("player.name");
+        let mut runner = StoryRunner::new(&graph, World::empty(), "FooNode");
+        let asked = |step: Step| match step {
+            Step::Ask(question) => question.text,
+            step => panic!("expected a question, got {:?}", step),
+        };
+        assert_eq!(asked(runner.step()), "What is your name?");
+        assert_eq!(runner.answer_text("  "), Err(AnswerError::EmptyAnswer));
+        assert_eq!(asked(runner.step()), "What is your name?");
+        runner.answer_text("Ada").unwrap();
+        assert_eq!(asked(runner.step()), "How many coins?");
+        assert_eq!(
+            runner.answer_text("9"),
+            Err(AnswerError::NumberOutOfRange { min: 1, max: 5 })
+        );
+        assert_eq!(asked(runner.step()), "How many coins?");
+        runner.answer_text("3").unwrap();
+        assert_eq!(
+            runner.step(),
+            Step::Output(String::from("Ada has 3 coins."))
+        );
+    }
+
+    #[test]
+    pub fn checks_answers() {
+        let graph = question_graph("player.name");
+        let mut runner = StoryRunner::new(&graph, World::empty(), "FooNode");
+        assert_eq!(
+            runner.answer_text("Ada"),
+            Err(AnswerError::NoPendingQuestion)
+        );
+        runner.step();
+        assert_eq!(runner.answer(0), Err(AnswerError::NoPendingPrompt));
+        assert_eq!(runner.answer_text("  "), Err(AnswerError::EmptyAnswer));
+        assert_eq!(runner.answer_text(" Ada "), Ok(()));
+        assert_eq!(runner.world().player.name, "Ada");
+        assert_eq!(
+            runner.step(),
+            Step::Ask(Question {
+                text: String::from("How many coins?"),
+                kind: AskKind::Number { min: 1, max: 5 },
+            })
+        );
+        assert_eq!(
+            runner.answer_text("six"),
+            Err(AnswerError::NumberOutOfRange { min: 1, max: 5 })
+        );
+        assert_eq!(runner.answer_text("5"), Ok(()));
+        assert_eq!(
+            runner.step(),
+            Step::Output(String::from("Ada has 5 coins."))
+        );
+    }
+
+    #[test]
+    pub fn reports_questions_that_cannot_be_answered() {
+        let graph = question_graph("player.nmae");
+        let mut runner = StoryRunner::new(&graph, World::empty(), "FooNode");
+        runner.step();
+        assert_eq!(runner.answer_text("Ada"), Ok(()));
+        assert!(!runner.is_finished());
+        assert_eq!(
+            runner.step(),
+            Step::Error(StoryError::CannotAssign(String::from("player.nmae")))
+        );
+        assert_eq!(runner.step(), Step::Finished);
+    }
+
+    fn path_graph() -> StoryGraph {
+        let mut graph = StoryGraph::new();
+        for (name, next_node) in &[("Top", "Middle"), ("Middle", "Bottom")] {
+            graph.add_node(StoryNode::new(
+                name.to_string(),
+                vec![StoryElement::Choice(StoryChoice::new(vec![
+                    StoryOption::new(
+                        Template::raw_from_str("Go down."),
+                        None,
+                        Some(next_node.to_string()),
+                    ),
+                    StoryOption::new(
+                        Template::raw_from_str("Stay."),
+                        None,
+                        Some(name.to_string()),
+                    ),
+                ]))],
+            ));
+        }
+        graph.add_node(StoryNode::new(
+            "Bottom".to_string(),
+            vec![
+                StoryElement::Choice(StoryChoice::new(vec![StoryOption::new(
+                    Template::raw_from_str("Leave."),
+                    None,
+                    None,
+                )])),
+                StoryElement::Exit,
+            ],
+        ));
+        graph
+    }
+
+    fn node_of(runner: &StoryRunner) -> String {
+        runner.cursor().unwrap().node.clone()
+    }
+
+    #[test]
+    pub fn undoes_choices_and_world_changes() {
+        let graph = path_graph();
+        let mut runner = StoryRunner::new(&graph, World::empty(), "Top");
+        assert_eq!(
+            runner.undo(),
+            CommandResponse::Continue(String::from("[[There is nothing to undo.]]"))
+        );
+        runner.step();
+        runner.answer(0).unwrap();
+        runner
+            .world_mut()
+            .player
+            .stats
+            .mut_stat(StatKind::Strength)
+            .advance(1000);
+        let strength = |runner: &StoryRunner| runner.world().player.stats.print_table();
+        let advanced = strength(&runner);
+        runner.step();
+        runner.answer(0).unwrap();
+        assert_eq!(node_of(&runner), "Bottom");
+        // Undoing before the next choice is offered returns to the last one.
+        runner.command(Command::Undo);
+        assert_eq!(node_of(&runner), "Middle");
+        assert_eq!(strength(&runner), advanced);
+        runner.step();
+        // At a choice, undo takes back the choice before it, and the stat
+        // progress gained since.
+        runner.command(Command::Undo);
+        assert_eq!(node_of(&runner), "Top");
+        assert_eq!(strength(&runner), World::empty().player.stats.print_table());
+        assert_eq!(
+            runner.step(),
+            Step::Prompt(vec![
+                PromptOption {
+                    id: 0,
+                    text: String::from("Go down."),
+                },
+                PromptOption {
+                    id: 1,
+                    text: String::from("Stay."),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    pub fn keeps_bounded_history() {
+        let graph = path_graph();
+        let mut runner = StoryRunner::new(&graph, World::empty(), "Top");
+        runner.set_history_limit(2);
+        for _ in 0..3 {
+            runner.step();
+            runner.answer(1).unwrap();
+        }
+        runner.step();
+        runner.answer(0).unwrap();
+        runner.step();
+        assert_eq!(node_of(&runner), "Middle");
+        runner.undo();
+        assert_eq!(node_of(&runner), "Top");
+        runner.step();
+        assert_eq!(
+            runner.undo(),
+            CommandResponse::Continue(String::from("[[There is nothing to undo.]]"))
+        );
+    }
+
+    #[test]
+    pub fn rewinds_to_earlier_nodes() {
+        let graph = path_graph();
+        let mut interface = TestInterface::with_inputs(VecDeque::from(vec![
+            TestInput::Choice(0),
+            TestInput::Choice(0),
+            TestInput::Command(Command::Rewind),
+            // Newest first: Middle, Top, then "Stay here."
+            TestInput::Choice(1),
+            // Back at Top, stay there.
+            TestInput::Choice(1),
+            TestInput::Command(Command::Rewind),
+            TestInput::Choice(1),
+            TestInput::Choice(0),
+            TestInput::Choice(0),
+            TestInput::Choice(0),
+        ]));
+        let mut runner = StoryRunner::new(&graph, World::empty(), "Top");
+        run(&mut runner, &mut interface).unwrap();
+        assert_eq!(
+            interface.written,
+            "Where do you want to go back to?\
+             Where do you want to go back to?\
+             Goodbye! Thanks for playing."
+        );
+
+        let mut runner = StoryRunner::new(&graph, World::empty(), "Top");
+        runner.step();
+        runner.answer(0).unwrap();
+        runner.step();
+        assert!(!runner.rewind_to("Bottom"));
+        assert!(runner.rewind_to("Top"));
+        assert_eq!(node_of(&runner), "Top");
+    }
+
+    #[test]
+    pub fn staying_after_a_rewind_keeps_the_history() {
+        let graph = path_graph();
+        let mut runner = StoryRunner::new(&graph, World::empty(), "Top");
+        runner.step();
+        runner.answer(0).unwrap();
+        let middle = runner.step();
+        runner.command(Command::Rewind);
+        // "Go back to Top." and "Stay here."
+        runner.answer(1).unwrap();
+        assert_eq!(runner.step(), middle);
+        assert_eq!(
+            runner.undo(),
+            CommandResponse::Continue(String::from("[[You take back your last choice.]]"))
+        );
+        assert_eq!(node_of(&runner), "Top");
+        runner.step();
+        assert_eq!(
+            runner.undo(),
+            CommandResponse::Continue(String::from("[[There is nothing to undo.]]"))
+        );
+    }
+
+    fn option_ids(step: Step) -> Vec<usize> {
+        match step {
+            Step::Prompt(options) => options.iter().map(|option| option.id).collect(),
+            step => panic!("expected a prompt, got {:?}", step),
+        }
+    }
+
+    #[test]
+    pub fn runs_directives_and_conditions() {
+        let graph = StoryGraph::parse(
+            "Start\n----\n\n\
+             | SwitchCheck Perception\n\n\
+             0. You see a little.\n\n\
+             5. You see a lot.\n\n\
+             > ?Marker Warned\n\
+             > 'Heed the warning.'\n\
+             > Goto -> End\n\n\
+             > 'Sneak.'\n\
+             > Check Sneak 1 -> End Caught\n\n\
+             Caught\n----\n\n\
+             > Mark Warned\n\n\
+             You are caught.\n\n\
+             ? Marker !Warned\n\n\
+             Never shown.\n\n\
+             > Goto -> Start\n\n\
+             End\n----\n\n\
+             > Exit\n",
+        )
+        .unwrap();
+        let mut runner = StoryRunner::new(&graph, World::empty(), "Start");
+        assert_eq!(
+            runner.step(),
+            Step::Output(String::from("You see a little."))
+        );
+        assert_eq!(option_ids(runner.step()), vec![1]);
+        runner.answer(1).unwrap();
+        assert_eq!(runner.step(), Step::Output(String::from("You are caught.")));
+        assert!(runner.world().markers.is_set("Warned"));
+        assert_eq!(
+            runner.step(),
+            Step::Output(String::from("You see a little."))
+        );
+        assert_eq!(option_ids(runner.step()), vec![0, 1]);
+        runner.answer(0).unwrap();
+        assert_eq!(
+            runner.step(),
+            Step::Output(String::from("[[Goodbye! Thanks for playing.]]"))
+        );
+        assert!(runner.is_finished());
+    }
+
+    #[test]
+    pub fn reports_endless_loops_and_unsupported_directives() {
+        let graph = StoryGraph::parse(
+            "Loop\n----\n\n> Goto -> Loop\n\n\
+             Fight\n----\n\n> Combat {\n> 1x Wolf\n> } -> Won Lost\n",
+        )
+        .unwrap();
+        let mut runner = StoryRunner::new(&graph, World::empty(), "Loop");
+        assert_eq!(
+            runner.step(),
+            Step::Error(StoryError::EndlessLoop(String::from("Loop")))
+        );
+        let mut runner = StoryRunner::new(&graph, World::empty(), "Fight");
+        assert_eq!(
+            runner.step(),
+            Step::Error(StoryError::Unsupported(String::from("Combat")))
+        );
+    }
+
+    #[test]
+    pub fn starts_with_the_title_screen() {
+        let graph = StoryGraph::parse(
+            "| Title Two Rooms\n| Start Second\n\n\
+             First\n----\n\n> Exit\n\n\
+             Second\n----\n\nThe second room.\n\n> Exit\n",
+        )
+        .unwrap();
+        let mut runner = StoryRunner::start(&graph, World::empty());
+        assert_eq!(runner.step(), Step::Output(String::from("*Two Rooms*")));
+        assert_eq!(
+            runner.step(),
+            Step::Output(String::from("The second room."))
+        );
+    }
+
+    #[test]
+    pub fn saves_and_loads_playthroughs() {
+        let source = "| Version 1\n\n\
+                      Top\n----\n\n\
+                      > 'Mark it.'\n> Mark Done\n\n\
+                      > 'Leave.'\n\n\
+                      > Exit\n";
+        let graph = StoryGraph::parse(source).unwrap();
+        let mut runner = StoryRunner::new(&graph, World::empty(), "Top");
+        assert_eq!(option_ids(runner.step()), vec![0, 1]);
+        let save = runner.save().unwrap();
+        assert_eq!(save.story_version.as_deref(), Some("1"));
+        runner.answer(0).unwrap();
+        assert!(runner.world().markers.is_set("Done"));
+        runner.load(save.clone()).unwrap();
+        assert!(!runner.world().markers.is_set("Done"));
+        assert_eq!(option_ids(runner.step()), vec![0, 1]);
+
+        let newer = source
+            .replace("Version 1", "Version 2")
+            .replace("Top", "Summit");
+        let mut newer = StoryGraph::parse(&newer).unwrap();
+        let mut runner = StoryRunner::new(&newer, World::empty(), "Summit");
+        assert_eq!(
+            runner.load(save.clone()),
+            Err(SaveError::VersionMismatch {
+                saved: Some(String::from("1")),
+                story: Some(String::from("2")),
+            })
+        );
+
+        let mut rename = Migration {
+            from: String::from("1"),
+            to: String::from("2"),
+            ..Migration::default()
+        };
+        newer.set_migrations(Migrations {
+            steps: vec![rename.clone()],
+            fallback_node: None,
+        });
+        let mut runner = StoryRunner::new(&newer, World::empty(), "Summit");
+        runner.load(save.clone()).unwrap();
+        assert_eq!(
+            runner.step(),
+            Step::Output(String::from(
+                "[[The story has changed since you saved, and `Top` is gone. \
+                 You continue from `Summit`.]]"
+            ))
+        );
+        assert_eq!(option_ids(runner.step()), vec![0, 1]);
+
+        rename
+            .nodes
+            .insert(String::from("Top"), String::from("Summit"));
+        newer.set_migrations(Migrations {
+            steps: vec![rename],
+            fallback_node: None,
+        });
+        let mut runner = StoryRunner::new(&newer, World::empty(), "Summit");
+        runner.load(save).unwrap();
+        assert_eq!(option_ids(runner.step()), vec![0, 1]);
+        assert_eq!(runner.save().unwrap().story_version.as_deref(), Some("2"));
+    }
+
+    #[test]
+    pub fn save_commands_use_the_save_file() {
+        let graph = foo_choice_graph();
+        let path = std::env::temp_dir().join(format!("story-save-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut runner = StoryRunner::new(&graph, World::empty(), "FooNode");
+        runner.set_save_file(path.clone());
+        assert_eq!(
+            runner.command(Command::Load),
+            CommandResponse::Continue(String::from("[[There is no saved game.]]"))
+        );
+        runner.step();
+        assert_eq!(
+            runner.command(Command::Save),
+            CommandResponse::Continue(String::from("[[Game saved.]]"))
+        );
+        runner.answer(0).unwrap();
+        assert_eq!(
+            runner.command(Command::Load),
+            CommandResponse::Continue(String::from("[[Game loaded.]]"))
+        );
+        assert_eq!(option_ids(runner.step()), vec![0]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn save_commands_work_without_a_save_file() {
+        let graph = StoryGraph::parse(
+            "Top\n----\n\n\
+             > 'Mark it.'\n> Mark Done\n\n\
+             Marked.\n\n\
+             > 'Leave.'\n\n\
+             > Exit\n",
+        )
+        .unwrap();
+        let mut runner = StoryRunner::new(&graph, World::empty(), "Top");
+        let mut interface = TestInterface::with_inputs(VecDeque::from(vec![
+            TestInput::Command(Command::Load),
+            TestInput::Command(Command::Save),
+            TestInput::Choice(0),
+            TestInput::Command(Command::Load),
+        ]));
+        run(&mut runner, &mut interface).unwrap();
+        assert_eq!(
+            interface.written,
+            "There is no saved game.Game saved.Marked.Game loaded."
+        );
+        assert!(!runner.world().markers.is_set("Done"));
+    }
+
+    #[test]
+    pub fn calls_return_after_the_call() {
+        let graph = StoryGraph::parse(
+            "Cabin\n----\n\n\
+             You step inside.\n\n\
+             > Call ReadNote\n\n\
+             > 'Read it again.'\n\
+             > Call ReadNote\n\n\
+             > 'Leave.'\n\n\
+             You put the note away.\n\n\
+             > Exit\n\n\
+             ReadNote\n----\n\n\
+             The note says to run.\n\n\
+             > 'Fold it.'\n",
+        )
+        .unwrap();
+        let mut runner = StoryRunner::new(&graph, World::empty(), "Cabin");
+        let note = Step::Output(String::from("The note says to run."));
+        let away = Step::Output(String::from("You put the note away."));
+        assert_eq!(
+            runner.step(),
+            Step::Output(String::from("You step inside."))
+        );
+        assert_eq!(runner.step(), note);
+        assert_eq!(option_ids(runner.step()), vec![0]);
+        assert_eq!(
+            runner.calls(),
+            &[Cursor {
+                node: String::from("Cabin"),
+                element: 2,
+            }]
+        );
+        runner.answer(0).unwrap();
+        assert_eq!(option_ids(runner.step()), vec![0, 1]);
+        assert!(runner.calls().is_empty());
+
+        // Options return after their choice. Calls are saved, and undo goes
+        // back into them.
+        runner.answer(0).unwrap();
+        assert_eq!(runner.step(), note);
+        assert_eq!(option_ids(runner.step()), vec![0]);
+        let save = runner.save().unwrap();
+        assert_eq!(save.calls.len(), 1);
+        runner.answer(0).unwrap();
+        assert_eq!(runner.step(), away);
+        runner.undo();
+        assert_eq!(runner.calls().len(), 1);
+        assert_eq!(option_ids(runner.step()), vec![0]);
+        runner.stop();
+        runner.load(save).unwrap();
+        assert_eq!(option_ids(runner.step()), vec![0]);
+        runner.answer(0).unwrap();
+        assert_eq!(runner.step(), away);
+    }
+
+    #[test]
+    pub fn reports_calls_that_never_return() {
+        let graph = StoryGraph::parse("Echo\n----\n\nHello?\n\n> Call Echo\n").unwrap();
+        let mut runner = StoryRunner::new(&graph, World::empty(), "Echo");
+        for _ in 0..CALL_DEPTH_LIMIT + 1 {
+            assert_eq!(runner.step(), Step::Output(String::from("Hello?")));
+        }
+        assert_eq!(
+            runner.step(),
+            Step::Error(StoryError::CallTooDeep(String::from("Echo")))
+        );
+    }
+}
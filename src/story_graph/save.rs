@@ -0,0 +1,504 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+
+use crate::accessible::Accessible;
+use crate::inventory::Item;
+use crate::json::Json;
+use crate::stat::{StatKind, MAX_BASE_VALUE};
+use crate::story_graph::runner::Cursor;
+use crate::story_graph::{StoryElement, StoryGraph};
+use crate::world::World;
+
+// A playthrough as written to a save file: where the runner was, the calls
+// it was in, the world at that point, and the version of the story it was
+// playing.
+#[derive(Clone)]
+pub struct SaveGame {
+    pub story_version: Option<String>,
+    pub cursor: Cursor,
+    // Where each call under way returns to, innermost last.
+    pub calls: Vec<Cursor>,
+    pub world: World,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SaveError {
+    // The save file is not one this game wrote.
+    Malformed(String),
+    // No migration leads from the saved version of the story to this one.
+    VersionMismatch {
+        saved: Option<String>,
+        story: Option<String>,
+    },
+    // The saved place is not in the story, and there is nowhere else to go.
+    UnknownNode(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Malformed(message) => write!(f, "the save file is broken: {}", message),
+            SaveError::VersionMismatch { saved, story } => {
+                let version = |version: &Option<String>| match version {
+                    Some(version) => format!("version {}", version),
+                    None => String::from("an unversioned story"),
+                };
+                write!(
+                    f,
+                    "the game was saved in {}, which cannot be brought up to {}",
+                    version(saved),
+                    version(story)
+                )
+            }
+            SaveError::UnknownNode(node) => {
+                write!(
+                    f,
+                    "the game was saved at `{}`, which the story no longer has",
+                    node
+                )
+            }
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+// How saves from earlier versions of a story are brought up to date.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Migrations {
+    pub steps: Vec<Migration>,
+    // Where saves resume when their node is gone; the start node if None.
+    pub fallback_node: Option<String>,
+}
+
+// What changed from one version of the story to the next.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Migration {
+    pub from: String,
+    pub to: String,
+    // Old names to new ones.
+    pub nodes: HashMap<String, String>,
+    pub markers: HashMap<String, String>,
+}
+
+impl SaveGame {
+    // Applies the graph's migrations from the saved version on, which must
+    // lead to the graph's version. A save whose node is still gone resumes
+    // at the fallback node; the lost node is returned so that the player
+    // can be told.
+    pub fn migrate(&mut self, graph: &StoryGraph) -> Result<Option<String>, SaveError> {
+        let migrations = graph.migrations();
+        let saved_version = self.story_version.clone();
+        for _ in 0..migrations.steps.len() {
+            if self.story_version == graph.metadata().version {
+                break;
+            }
+            let step = migrations
+                .steps
+                .iter()
+                .find(|step| self.story_version.as_ref() == Some(&step.from));
+            let step = match step {
+                Some(step) => step,
+                None => break,
+            };
+            for cursor in self.calls.iter_mut().chain(Some(&mut self.cursor)) {
+                if let Some(node) = step.nodes.get(&cursor.node) {
+                    cursor.node = node.clone();
+                }
+            }
+            self.world.markers.rename(&step.markers);
+            self.story_version = Some(step.to.clone());
+        }
+        if self.story_version != graph.metadata().version {
+            return Err(SaveError::VersionMismatch {
+                saved: saved_version,
+                story: graph.metadata().version.clone(),
+            });
+        }
+        // Calls into nodes that are gone cannot be returned to; the story
+        // carries on from the call before.
+        self.calls
+            .retain(|caller| graph.find_node(&caller.node).is_some());
+        if let Some(node) = graph.find_node(&self.cursor.node) {
+            // Saves are made at choices and questions; if the node changed
+            // so that there is none at the saved place, start it over.
+            let resumable = match node.elements().get(self.cursor.element) {
+                Some(StoryElement::If(_, element)) => is_resumable(element),
+                Some(element) => is_resumable(element),
+                None => false,
+            };
+            if !resumable {
+                self.cursor.element = 0;
+            }
+            return Ok(None);
+        }
+        let fallback = migrations
+            .fallback_node
+            .as_deref()
+            .or_else(|| graph.start_node())
+            .filter(|node| graph.find_node(node).is_some());
+        match fallback {
+            Some(fallback) => {
+                self.calls.clear();
+                let lost = mem::replace(&mut self.cursor, Cursor::start_of(fallback));
+                Ok(Some(lost.node))
+            }
+            None => Err(SaveError::UnknownNode(self.cursor.node.clone())),
+        }
+    }
+
+    pub fn to_json(&self) -> Json {
+        let player = &self.world.player;
+        let stats = StatKind::all()
+            .iter()
+            .map(|kind| {
+                let stat = player.stats.stat(*kind);
+                (
+                    kind.display_name().to_string(),
+                    Json::object(vec![
+                        ("base", Json::Number(stat.base_value() as f64)),
+                        ("progress", Json::Number(stat.progress() as f64)),
+                    ]),
+                )
+            })
+            .collect();
+        let inventory = player
+            .inventory
+            .stacks()
+            .iter()
+            .map(|stack| {
+                Json::object(vec![
+                    ("id", Json::string(stack.item().id())),
+                    ("name", Json::string(stack.item().name())),
+                    ("count", Json::Number(stack.count() as f64)),
+                ])
+            })
+            .collect();
+        let vars = self
+            .world
+            .vars
+            .property_names()
+            .into_iter()
+            .map(|name| {
+                let value = self.world.vars.lookup_local(name).unwrap_or_default();
+                (name.to_string(), Json::String(value))
+            })
+            .collect();
+        let markers = self
+            .world
+            .markers
+            .property_names()
+            .into_iter()
+            .map(Json::string)
+            .collect();
+        let calls = self
+            .calls
+            .iter()
+            .map(|caller| {
+                Json::object(vec![
+                    ("node", Json::string(&caller.node)),
+                    ("element", Json::Number(caller.element as f64)),
+                ])
+            })
+            .collect();
+        Json::object(vec![
+            (
+                "story_version",
+                self.story_version
+                    .as_deref()
+                    .map_or(Json::Null, Json::string),
+            ),
+            ("node", Json::string(&self.cursor.node)),
+            ("element", Json::Number(self.cursor.element as f64)),
+            ("calls", Json::Array(calls)),
+            (
+                "player",
+                Json::object(vec![
+                    ("name", Json::string(&player.name)),
+                    ("stats", Json::Object(stats)),
+                    ("inventory", Json::Array(inventory)),
+                ]),
+            ),
+            ("vars", Json::Object(vars)),
+            ("markers", Json::Array(markers)),
+        ])
+    }
+
+    // Reads a save written by to_json. Items are taken from the graph's
+    // catalog when it has them.
+    pub fn from_json(json: &Json, graph: &StoryGraph) -> Result<SaveGame, SaveError> {
+        let story_version = match json.get("story_version") {
+            None | Some(Json::Null) => None,
+            Some(version) => Some(string(Some(version), "story_version")?),
+        };
+        let cursor = Cursor {
+            node: string(json.get("node"), "node")?,
+            element: number(json.get("element"), "element")?,
+        };
+        let mut calls = Vec::new();
+        for caller in array(json.get("calls"), "calls")? {
+            calls.push(Cursor {
+                node: string(caller.get("node"), "call node")?,
+                element: number(caller.get("element"), "call element")?,
+            });
+        }
+        let mut world = World::empty();
+        let player = json.get("player").ok_or_else(|| malformed("player"))?;
+        world.player.name = string(player.get("name"), "player name")?;
+        if let Some(Json::Object(stats)) = player.get("stats") {
+            for (name, stat) in stats {
+                let kind = StatKind::from_name(name).ok_or_else(|| malformed("stat name"))?;
+                let base = number(stat.get("base"), "stat base")?;
+                let progress = number(stat.get("progress"), "stat progress")?;
+                if base as i64 > MAX_BASE_VALUE {
+                    return Err(malformed("stat base"));
+                }
+                world
+                    .player
+                    .stats
+                    .mut_stat(kind)
+                    .restore(base as i64, progress as i64);
+            }
+        }
+        for stack in array(player.get("inventory"), "inventory")? {
+            let id = string(stack.get("id"), "item id")?;
+            let name = string(stack.get("name"), "item name")?;
+            let count = number(stack.get("count"), "item count")?;
+            let count = i64::try_from(count).map_err(|_| malformed("item count"))?;
+            let item = match graph.item(&id) {
+                Some(item) => item.clone(),
+                None => Item::new(id, name, Vec::new()),
+            };
+            world.player.inventory.add_items(item, count);
+        }
+        if let Some(Json::Object(vars)) = json.get("vars") {
+            for (name, value) in vars {
+                let value = string(Some(value), "variable")?;
+                world.vars.set_local(name, &value);
+            }
+        }
+        for marker in array(json.get("markers"), "markers")? {
+            world.markers.set(&string(Some(marker), "marker")?);
+        }
+        Ok(SaveGame {
+            story_version,
+            cursor,
+            calls,
+            world,
+        })
+    }
+}
+
+fn is_resumable(element: &StoryElement) -> bool {
+    matches!(element, StoryElement::Choice(_) | StoryElement::Ask(_))
+}
+
+fn malformed(what: &str) -> SaveError {
+    SaveError::Malformed(format!("bad or missing {}", what))
+}
+
+fn string(json: Option<&Json>, what: &str) -> Result<String, SaveError> {
+    json.and_then(Json::as_str)
+        .map(String::from)
+        .ok_or_else(|| malformed(what))
+}
+
+fn number(json: Option<&Json>, what: &str) -> Result<usize, SaveError> {
+    json.and_then(Json::as_usize).ok_or_else(|| malformed(what))
+}
+
+fn array<'a>(json: Option<&'a Json>, what: &str) -> Result<&'a [Json], SaveError> {
+    match json {
+        None => Ok(&[]),
+        Some(json) => json.as_array().ok_or_else(|| malformed(what)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accessible::AccessPath;
+    use crate::inventory::{ItemBehavior, KeyData};
+
+    #[test]
+    fn round_trips_through_json() {
+        let mut graph = StoryGraph::new();
+        let key = Item::new(
+            String::from("Key"),
+            String::from("a key"),
+            vec![ItemBehavior::Key(KeyData::new(vec![String::from("Door")]))],
+        );
+        graph.add_item(key.clone());
+        let mut world = World::empty();
+        world.player.stats.mut_stat(StatKind::Will).advance(250);
+        world.player.inventory.add_item(key.clone());
+        world.player.inventory.add_item(key);
+        world.player.inventory.add_item(Item::new(
+            String::from("Rock"),
+            String::from("a rock"),
+            vec![],
+        ));
+        world.assign(AccessPath::from(String::from("vars.name")).view(), "Ash");
+        world.markers.set("MetStan");
+        let save = SaveGame {
+            story_version: Some(String::from("2")),
+            cursor: Cursor {
+                node: String::from("Cabin"),
+                element: 3,
+            },
+            calls: vec![Cursor {
+                node: String::from("Porch"),
+                element: 2,
+            }],
+            world,
+        };
+        let text = save.to_json().to_string();
+        let loaded = SaveGame::from_json(&Json::parse(&text).unwrap(), &graph).unwrap();
+        assert_eq!(loaded.story_version.as_deref(), Some("2"));
+        assert_eq!(loaded.cursor, save.cursor);
+        assert_eq!(loaded.calls, save.calls);
+        assert_eq!(loaded.to_json(), save.to_json());
+        let keys = loaded
+            .world
+            .player
+            .inventory
+            .get_stack_by_id("Key")
+            .unwrap();
+        assert_eq!(keys.count(), 2);
+        assert!(!keys.item().behaviors().is_empty());
+        assert!(loaded.world.markers.is_set("MetStan"));
+    }
+
+    fn save_at(node: &str, element: usize, version: &str) -> SaveGame {
+        let mut world = World::empty();
+        world.markers.set("SawStan");
+        SaveGame {
+            story_version: Some(String::from(version)),
+            cursor: Cursor {
+                node: String::from(node),
+                element,
+            },
+            calls: Vec::new(),
+            world,
+        }
+    }
+
+    #[test]
+    fn migrates_old_saves() {
+        let mut graph = StoryGraph::parse(
+            "| Version 3\n| Start Top\n\n\
+             Top\n----\n\n> 'Wait.'\n\n\
+             Cabin\n----\n\nInside.\n\n> 'Sit.'\n\n\
+             Porch\n----\n\n> 'Wait.'\n",
+        )
+        .unwrap();
+        let step = |from: &str, to: &str, node: (&str, &str), marker: (&str, &str)| Migration {
+            from: String::from(from),
+            to: String::from(to),
+            nodes: vec![(String::from(node.0), String::from(node.1))]
+                .into_iter()
+                .collect(),
+            markers: vec![(String::from(marker.0), String::from(marker.1))]
+                .into_iter()
+                .collect(),
+        };
+        graph.set_migrations(Migrations {
+            steps: vec![
+                step("2", "3", ("Hut", "Cabin"), ("MetStan", "KnowsStan")),
+                step("1", "2", ("Shack", "Hut"), ("SawStan", "MetStan")),
+            ],
+            fallback_node: Some(String::from("Porch")),
+        });
+
+        let mut save = save_at("Shack", 1, "1");
+        assert_eq!(save.migrate(&graph), Ok(None));
+        assert_eq!(
+            save.cursor,
+            Cursor {
+                node: String::from("Cabin"),
+                element: 1,
+            }
+        );
+        assert_eq!(save.story_version.as_deref(), Some("3"));
+        assert!(save.world.markers.is_set("KnowsStan"));
+        assert!(!save.world.markers.is_set("SawStan"));
+
+        // Calls return to renamed nodes; calls from deleted nodes are gone.
+        let mut save = save_at("Top", 0, "2");
+        save.calls = vec![Cursor::start_of("Hut"), Cursor::start_of("Attic")];
+        assert_eq!(save.migrate(&graph), Ok(None));
+        assert_eq!(save.calls, vec![Cursor::start_of("Cabin")]);
+
+        // The saved element is no longer a choice.
+        let mut save = save_at("Top", 4, "3");
+        assert_eq!(save.migrate(&graph), Ok(None));
+        assert_eq!(save.cursor, Cursor::start_of("Top"));
+
+        let mut save = save_at("Attic", 2, "2");
+        assert_eq!(save.migrate(&graph), Ok(Some(String::from("Attic"))));
+        assert_eq!(save.cursor, Cursor::start_of("Porch"));
+
+        // Saves from versions no migration leads on from.
+        for version in &["0", "4"] {
+            let mut save = save_at("Top", 0, version);
+            assert_eq!(
+                save.migrate(&graph),
+                Err(SaveError::VersionMismatch {
+                    saved: Some(version.to_string()),
+                    story: Some(String::from("3")),
+                })
+            );
+        }
+
+        graph.set_migrations(Migrations::default());
+        let mut save = save_at("Attic", 2, "3");
+        assert_eq!(save.migrate(&graph), Ok(Some(String::from("Attic"))));
+        assert_eq!(save.cursor, Cursor::start_of("Top"));
+        let mut save = save_at("Top", 0, "2");
+        assert_eq!(
+            save.migrate(&graph).unwrap_err().to_string(),
+            "the game was saved in version 2, which cannot be brought up to version 3"
+        );
+        let mut save = save_at("Attic", 2, "2");
+        save.story_version = None;
+        assert_eq!(
+            save.migrate(&StoryGraph::new()).err(),
+            Some(SaveError::UnknownNode(String::from("Attic")))
+        );
+    }
+
+    #[test]
+    fn rejects_broken_saves() {
+        let graph = StoryGraph::new();
+        let load = |text: &str| SaveGame::from_json(&Json::parse(text).unwrap(), &graph);
+        assert_eq!(
+            load(r#"{"node": "Top", "element": -1}"#).err(),
+            Some(SaveError::Malformed(String::from("bad or missing element")))
+        );
+        assert_eq!(
+            load(r#"{"node": "Top", "element": 0}"#).err(),
+            Some(SaveError::Malformed(String::from("bad or missing player")))
+        );
+        let with_rocks = |count: &str| {
+            load(&format!(
+                r#"{{"node": "Top", "element": 0, "player": {{"name": "Ash",
+                    "inventory": [{{"id": "Rock", "name": "a rock", "count": {}}}]}}}}"#,
+                count
+            ))
+        };
+        let save = with_rocks("1e15").unwrap();
+        let rocks = save.world.player.inventory.get_stack_by_id("Rock").unwrap();
+        assert_eq!(rocks.count(), 1_000_000_000_000_000);
+        for count in &["1e19", "-1", "2.5"] {
+            assert_eq!(
+                with_rocks(count).err(),
+                Some(SaveError::Malformed(String::from(
+                    "bad or missing item count"
+                )))
+            );
+        }
+    }
+}
@@ -35,10 +35,7 @@ impl<'a, 'b, 'c, T: 'a> Table<'b, 'c, T> {
             }
             table.push(row);
         }
-        let mut max_length: Vec<usize> = Vec::new();
-        for _ in &self.columns {
-            max_length.push(0);
-        }
+        let mut max_length: Vec<usize> = vec![0; self.columns.len()];
         for row in table.iter() {
             for (i, cell) in row.iter().enumerate() {
                 if max_length[i] < cell.len() {
@@ -48,23 +45,23 @@ impl<'a, 'b, 'c, T: 'a> Table<'b, 'c, T> {
         }
         let mut table_string = String::new();
         for row in table {
-            table_string.push_str("|");
+            table_string.push('|');
             for (i, cell) in row.iter().enumerate() {
-                table_string.push_str(" ");
+                table_string.push(' ');
                 if self.columns[i].alignment == ColumnAlignment::Right {
                     for _ in 0..(max_length[i] - cell.len()) {
-                        table_string.push_str(" ");
+                        table_string.push(' ');
                     }
                 }
                 table_string.push_str(cell);
                 if self.columns[i].alignment == ColumnAlignment::Left {
                     for _ in 0..(max_length[i] - cell.len()) {
-                        table_string.push_str(" ");
+                        table_string.push(' ');
                     }
                 }
                 table_string.push_str(" |");
             }
-            table_string.push_str("\n");
+            table_string.push('\n');
         }
         table_string
     }
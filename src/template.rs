@@ -1,11 +1,127 @@
+pub mod filter;
+
+use std::error::Error;
+use std::fmt;
+
 use crate::accessible::{AccessPath, Accessible};
+use filter::Filter;
 
 #[derive(Clone)]
 pub enum Token {
     Text(String),
     Accessor(AccessPath),
+    // An accessed value passed through filters, left to right.
+    Filtered(AccessPath, Vec<Filter>),
+    // Renders the first template if the condition holds, else the second.
+    If(Condition, Template, Template),
+    // Renders the template once per element of the list at the path, with
+    // the element available as a child under the given name.
+    For(String, AccessPath, Template),
+}
+
+// A path is truthy if it resolves to anything other than "", "0" or "false".
+#[derive(Clone, Debug, PartialEq)]
+pub struct Condition {
+    pub path: AccessPath,
+    pub negated: bool,
+}
+
+impl Condition {
+    pub fn holds(&self, accessible: &dyn Accessible) -> bool {
+        let truthy = match accessible.lookup(self.path.view()) {
+            Some(value) => !matches!(value.trim(), "" | "0" | "false"),
+            None => false,
+        };
+        truthy != self.negated
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderMode {
+    // Unresolved paths, and values a filter cannot apply to, render as
+    // nothing.
+    Lenient,
+    // Unresolved paths and failed filters are reported as a RenderError.
+    Strict,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnresolvedPath {
+    pub path: AccessPath,
+    // Index into Template.tokens.
+    pub token_index: usize,
+    // Byte offset into the rendered text where the value was expected.
+    pub offset: usize,
 }
 
+// A filter that could not handle the value it was given, e.g. `number` on
+// a name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FailedFilter {
+    pub path: AccessPath,
+    pub filter: Filter,
+    // The value as it reached the filter.
+    pub value: String,
+    // Index into Template.tokens.
+    pub token_index: usize,
+    // Byte offset into the rendered text where the value was expected.
+    pub offset: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderError {
+    pub unresolved: Vec<UnresolvedPath>,
+    pub failed_filters: Vec<FailedFilter>,
+}
+
+impl RenderError {
+    fn is_empty(&self) -> bool {
+        self.unresolved.is_empty() && self.failed_filters.is_empty()
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut lines = Vec::new();
+        if !self.unresolved.is_empty() {
+            lines.push(String::from("unresolved template paths:"));
+        }
+        for unresolved in &self.unresolved {
+            lines.push(format!(
+                "  {{{}}} (token {}, offset {})",
+                unresolved.path, unresolved.token_index, unresolved.offset
+            ));
+        }
+        if !self.failed_filters.is_empty() {
+            lines.push(String::from("failed template filters:"));
+        }
+        for failed in &self.failed_filters {
+            lines.push(format!(
+                "  `{}` on {{{}}} = `{}` (token {}, offset {})",
+                failed.filter, failed.path, failed.value, failed.token_index, failed.offset
+            ));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl Error for RenderError {}
+
+#[derive(Debug, PartialEq)]
+pub struct TemplateParseError {
+    pub message: String,
+    // Byte offset into the template source.
+    pub offset: usize,
+}
+
+impl fmt::Display for TemplateParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl Error for TemplateParseError {}
+
 #[derive(Clone)]
 pub struct Template {
     pub tokens: Vec<Token>,
@@ -19,49 +135,466 @@ impl Template {
         }
     }
 
-    pub fn render(&self, accessible: &Accessible) -> String {
+    // Parses text with `{path}` and `{path | filter | filter:"arg"}`
+    // accessors, `{if path}...{else}...{end}` conditionals and
+    // `{for name in path}...{end}` loops. `{{` and `}}` are literal braces.
+    pub fn parse(source: &str) -> Result<Template, TemplateParseError> {
+        let mut position = 0;
+        let (template, block_end) = parse_block(source, &mut position)?;
+        match block_end {
+            BlockEnd::Eof => Ok(template),
+            BlockEnd::Else(offset) => Err(TemplateParseError {
+                message: String::from("`{else}` outside of `{if}`"),
+                offset,
+            }),
+            BlockEnd::End(offset) => Err(TemplateParseError {
+                message: String::from("`{end}` without a matching block"),
+                offset,
+            }),
+        }
+    }
+
+    // Paths the template looks up. Paths inside a `{for}` body that start
+    // with the loop variable are left out, as they name list elements.
+    pub fn access_paths(&self) -> Vec<&AccessPath> {
+        let mut paths = Vec::new();
+        self.collect_access_paths(&[], &mut paths);
+        paths
+    }
+
+    fn collect_access_paths<'a>(&'a self, loop_names: &[&str], paths: &mut Vec<&'a AccessPath>) {
+        for token in &self.tokens {
+            let path = match token {
+                Token::Text(_) => continue,
+                Token::Accessor(path) | Token::Filtered(path, _) => path,
+                Token::If(condition, _, _) => &condition.path,
+                Token::For(_, path, _) => path,
+            };
+            if !loop_names.contains(&path.view().segment(0)) {
+                paths.push(path);
+            }
+            match token {
+                Token::If(_, then, otherwise) => {
+                    then.collect_access_paths(loop_names, paths);
+                    otherwise.collect_access_paths(loop_names, paths);
+                }
+                Token::For(name, _, body) => {
+                    let mut loop_names = loop_names.to_vec();
+                    loop_names.push(name);
+                    body.collect_access_paths(&loop_names, paths);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn render(&self, accessible: &dyn Accessible) -> String {
+        let mut rendered = String::new();
+        self.render_into(accessible, &mut rendered, &mut RenderError::default());
+        rendered
+    }
+
+    pub fn render_strict(&self, accessible: &dyn Accessible) -> Result<String, RenderError> {
         let mut rendered = String::new();
+        let mut errors = RenderError::default();
+        self.render_into(accessible, &mut rendered, &mut errors);
+        if errors.is_empty() {
+            Ok(rendered)
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn render_with_mode(
+        &self,
+        accessible: &dyn Accessible,
+        mode: RenderMode,
+    ) -> Result<String, RenderError> {
+        match mode {
+            RenderMode::Lenient => Ok(self.render(accessible)),
+            RenderMode::Strict => self.render_strict(accessible),
+        }
+    }
+
+    // Error token indices are relative to the innermost template containing
+    // them; offsets are into the full rendered text.
+    fn render_into(
+        &self,
+        accessible: &dyn Accessible,
+        rendered: &mut String,
+        errors: &mut RenderError,
+    ) {
+        for (token_index, token) in self.tokens.iter().enumerate() {
+            match token {
+                Token::Text(string) => rendered.push_str(string),
+                Token::Accessor(path) | Token::Filtered(path, _) => {
+                    match render_accessor(token, path, accessible) {
+                        Ok(value) => rendered.push_str(&value),
+                        Err(None) => errors.unresolved.push(UnresolvedPath {
+                            path: path.clone(),
+                            token_index,
+                            offset: rendered.len(),
+                        }),
+                        Err(Some((filter, value))) => errors.failed_filters.push(FailedFilter {
+                            path: path.clone(),
+                            filter: filter.clone(),
+                            value,
+                            token_index,
+                            offset: rendered.len(),
+                        }),
+                    }
+                }
+                Token::If(condition, then, otherwise) => {
+                    if condition.holds(accessible) {
+                        then.render_into(accessible, rendered, errors);
+                    } else {
+                        otherwise.render_into(accessible, rendered, errors);
+                    }
+                }
+                Token::For(name, path, body) => match accessible.lookup_list(path.view()) {
+                    Some(elements) => {
+                        for element in elements {
+                            let scope = Scope {
+                                name,
+                                value: element,
+                                outer: accessible,
+                            };
+                            body.render_into(&scope, rendered, errors);
+                        }
+                    }
+                    None => errors.unresolved.push(UnresolvedPath {
+                        path: path.clone(),
+                        token_index,
+                        offset: rendered.len(),
+                    }),
+                },
+            }
+        }
+    }
+}
+
+// Writes the template back in the syntax Template::parse reads, so that
+// parsing the output gives the same template.
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for token in &self.tokens {
             match token {
-                Token::Text(string) => rendered.push_str(&string),
-                Token::Accessor(path) => {
-                    if let Some(value) = accessible.lookup(path.view()) {
-                        rendered.push_str(&value);
+                Token::Text(text) => write!(f, "{}", text.replace('{', "{{").replace('}', "}}"))?,
+                Token::Accessor(path) => write!(f, "{{{}}}", path)?,
+                Token::Filtered(path, filters) => {
+                    write!(f, "{{{}", path)?;
+                    for filter in filters {
+                        write!(f, " | {}", filter)?;
+                    }
+                    write!(f, "}}")?;
+                }
+                Token::If(condition, then, otherwise) => {
+                    let negation = if condition.negated { "!" } else { "" };
+                    write!(f, "{{if {}{}}}{}", negation, condition.path, then)?;
+                    if !otherwise.tokens.is_empty() {
+                        write!(f, "{{else}}{}", otherwise)?;
                     }
+                    write!(f, "{{end}}")?;
+                }
+                Token::For(name, path, body) => {
+                    write!(f, "{{for {} in {}}}{}{{end}}", name, path, body)?
                 }
             }
         }
-        rendered
+        Ok(())
+    }
+}
+
+// Fails with None when the path is unresolved, or with the filter that
+// failed and the value it was given.
+fn render_accessor<'t>(
+    token: &'t Token,
+    path: &AccessPath,
+    accessible: &dyn Accessible,
+) -> Result<String, Option<(&'t Filter, String)>> {
+    let mut value = accessible.lookup(path.view()).ok_or(None)?;
+    if let Token::Filtered(_, filters) = token {
+        for filter in filters {
+            value = match filter.apply(&value) {
+                Some(filtered) => filtered,
+                None => return Err(Some((filter, value))),
+            };
+        }
+    }
+    Ok(value)
+}
+
+// Exposes a loop element under its loop variable name on top of the
+// accessible the template is rendered against.
+struct Scope<'a> {
+    name: &'a str,
+    value: &'a dyn Accessible,
+    outer: &'a dyn Accessible,
+}
+
+impl<'a> Accessible for Scope<'a> {
+    fn lookup_local(&self, property: &str) -> Option<String> {
+        self.outer.lookup_local(property)
+    }
+    fn get_child(&self, child: &str) -> Option<&dyn Accessible> {
+        if child == self.name {
+            Some(self.value)
+        } else {
+            self.outer.get_child(child)
+        }
+    }
+    fn get_list(&self, list: &str) -> Option<Vec<&dyn Accessible>> {
+        self.outer.get_list(list)
+    }
+    fn property_names(&self) -> Vec<&str> {
+        self.outer.property_names()
+    }
+    fn child_names(&self) -> Vec<&str> {
+        let mut names = self.outer.child_names();
+        names.push(self.name);
+        names
+    }
+}
+
+enum BlockEnd {
+    Eof,
+    // Offsets of the `{else}` or `{end}` tag.
+    Else(usize),
+    End(usize),
+}
+
+// Parses tokens from position up to the end of the source or an `{else}` or
+// `{end}` tag, leaving position just past whatever ended the block.
+fn parse_block(
+    source: &str,
+    position: &mut usize,
+) -> Result<(Template, BlockEnd), TemplateParseError> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    while let Some(c) = source[*position..].chars().next() {
+        let offset = *position;
+        let next = source[offset + c.len_utf8()..].chars().next();
+        match c {
+            '{' | '}' if next == Some(c) => {
+                *position += 2;
+                text.push(c);
+            }
+            '{' => {
+                // A `}` inside a quoted filter argument does not close the tag.
+                let end = match find_unquoted(&source[offset..], '}') {
+                    Some(length) => offset + length,
+                    None => {
+                        return Err(TemplateParseError {
+                            message: String::from("unclosed `{`"),
+                            offset,
+                        })
+                    }
+                };
+                *position = end + 1;
+                if !text.is_empty() {
+                    tokens.push(Token::Text(text.split_off(0)));
+                }
+                let body = &source[offset + 1..end];
+                let body_offset = offset + 1;
+                match body.trim() {
+                    "else" => return Ok((Template { tokens }, BlockEnd::Else(offset))),
+                    "end" => return Ok((Template { tokens }, BlockEnd::End(offset))),
+                    tag if tag.starts_with("if ") => {
+                        let condition = parse_condition(&tag[3..], body_offset)?;
+                        let (then, block_end) = parse_block(source, position)?;
+                        let otherwise = match block_end {
+                            BlockEnd::Else(_) => {
+                                let (otherwise, block_end) = parse_block(source, position)?;
+                                expect_end(block_end, "if", offset)?;
+                                otherwise
+                            }
+                            block_end => {
+                                expect_end(block_end, "if", offset)?;
+                                Template { tokens: Vec::new() }
+                            }
+                        };
+                        tokens.push(Token::If(condition, then, otherwise));
+                    }
+                    tag if tag.starts_with("for ") => {
+                        let (name, path) = parse_for(&tag[4..], body_offset)?;
+                        let (body, block_end) = parse_block(source, position)?;
+                        expect_end(block_end, "for", offset)?;
+                        tokens.push(Token::For(name, path, body));
+                    }
+                    _ => tokens.push(parse_accessor(body, body_offset)?),
+                }
+            }
+            '}' => {
+                return Err(TemplateParseError {
+                    message: String::from("unmatched `}`"),
+                    offset,
+                })
+            }
+            _ => {
+                *position += c.len_utf8();
+                text.push(c);
+            }
+        }
+    }
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+    Ok((Template { tokens }, BlockEnd::Eof))
+}
+
+fn expect_end(block_end: BlockEnd, block: &str, offset: usize) -> Result<(), TemplateParseError> {
+    match block_end {
+        BlockEnd::End(_) => Ok(()),
+        BlockEnd::Else(else_offset) => Err(TemplateParseError {
+            message: format!("`{{else}}` is not allowed here in `{{{}}}`", block),
+            offset: else_offset,
+        }),
+        BlockEnd::Eof => Err(TemplateParseError {
+            message: format!("`{{{}}}` is missing its `{{end}}`", block),
+            offset,
+        }),
+    }
+}
+
+fn parse_path(path: &str, offset: usize) -> Result<AccessPath, TemplateParseError> {
+    if path.is_empty()
+        || !path
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    {
+        return Err(TemplateParseError {
+            message: format!("`{}` is not a valid path", path),
+            offset,
+        });
+    }
+    Ok(AccessPath::from(path.to_string()))
+}
+
+// `path` or `!path`.
+fn parse_condition(condition: &str, offset: usize) -> Result<Condition, TemplateParseError> {
+    let condition = condition.trim();
+    match condition.strip_prefix('!') {
+        Some(path) => Ok(Condition {
+            path: parse_path(path.trim(), offset)?,
+            negated: true,
+        }),
+        None => Ok(Condition {
+            path: parse_path(condition, offset)?,
+            negated: false,
+        }),
     }
 }
 
+// `name in path`.
+fn parse_for(header: &str, offset: usize) -> Result<(String, AccessPath), TemplateParseError> {
+    let words: Vec<&str> = header.split_whitespace().collect();
+    match words.as_slice() {
+        [name, "in", path] if !name.contains('.') => {
+            Ok((name.to_string(), parse_path(path, offset)?))
+        }
+        _ => Err(TemplateParseError {
+            message: String::from("expected `{for name in path}`"),
+            offset,
+        }),
+    }
+}
+
+// Parses the inside of `{...}`; offset is the byte offset of body in the
+// template source.
+fn parse_accessor(body: &str, offset: usize) -> Result<Token, TemplateParseError> {
+    let error = |message: String| TemplateParseError { message, offset };
+    let mut sections = split_unquoted(body, '|').into_iter();
+    let path = parse_path(sections.next().unwrap_or("").trim(), offset)?;
+    let mut filters = Vec::new();
+    for section in sections {
+        let section = section.trim();
+        let (name, args) = match section.find(':') {
+            Some(colon) => (&section[..colon], parse_filter_args(&section[colon + 1..])),
+            None => (section, Ok(Vec::new())),
+        };
+        let args = args.map_err(error)?;
+        filters.push(Filter::parse(name.trim(), &args).map_err(error)?);
+    }
+    if filters.is_empty() {
+        Ok(Token::Accessor(path))
+    } else {
+        Ok(Token::Filtered(path, filters))
+    }
+}
+
+// Arguments are comma separated, either bare (`2`) or quoted (`"note"`).
+fn parse_filter_args(args: &str) -> Result<Vec<String>, String> {
+    let mut parsed = Vec::new();
+    for arg in split_unquoted(args, ',') {
+        let arg = arg.trim();
+        if arg.len() >= 2 && arg.starts_with('"') && arg.ends_with('"') {
+            parsed.push(arg[1..arg.len() - 1].to_string());
+        } else if arg.is_empty() || arg.contains('"') {
+            return Err(format!("malformed filter argument `{}`", arg));
+        } else {
+            parsed.push(arg.to_string());
+        }
+    }
+    Ok(parsed)
+}
+
+// Returns the byte offset of the first c in text outside double quotes.
+fn find_unquoted(text: &str, c: char) -> Option<usize> {
+    let mut quoted = false;
+    for (i, next) in text.char_indices() {
+        if next == '"' {
+            quoted = !quoted;
+        } else if next == c && !quoted {
+            return Some(i);
+        }
+    }
+    None
+}
+
+fn split_unquoted(text: &str, separator: char) -> Vec<&str> {
+    let mut sections = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            sections.push(&text[start..i]);
+            start = i + 1;
+        }
+    }
+    sections.push(&text[start..]);
+    sections
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::accessible::{AccessPath, Accessor};
+    use crate::inventory::Item;
+    use crate::world::World;
 
     struct TestContainer {
         a: String,
-        accessor: Accessor<TestContainer>,
     }
 
+    static TEST_CONTAINER_ACCESSOR: Accessor<TestContainer> =
+        Accessor::new(&[("a", |container| Some(container.a.clone()))]);
+
     impl TestContainer {
         fn new() -> TestContainer {
-            let mut container = TestContainer {
+            TestContainer {
                 a: String::from("foo"),
-                accessor: Accessor::new(),
-            };
-            container.accessor.register(
-                "a",
-                Box::new(|container: &TestContainer| Some(container.a.clone())),
-            );
-            container
+            }
         }
     }
 
     impl Accessible for TestContainer {
         fn lookup_local(&self, property: &str) -> Option<String> {
-            self.accessor.lookup(property, self)
+            TEST_CONTAINER_ACCESSOR.lookup(property, self)
+        }
+        fn property_names(&self) -> Vec<&str> {
+            TEST_CONTAINER_ACCESSOR.property_names()
         }
     }
 
@@ -96,4 +629,199 @@ mod tests {
         let container = TestContainer::new();
         assert_eq!(template.render(&container), "Value: (foo)");
     }
+
+    #[test]
+    fn parses_accessors() {
+        let template = Template::parse("Value: ({a}) {{braced}}").unwrap();
+        let container = TestContainer::new();
+        assert_eq!(template.render(&container), "Value: (foo) {braced}");
+        assert_eq!(template.access_paths().len(), 1);
+    }
+
+    #[test]
+    fn renders_filtered_values() {
+        let template = Template::parse("{a | upper}, {a|capitalize|plural:\"x\",\"y\"}").unwrap();
+        let container = TestContainer::new();
+        // `plural` needs a count, so leniently "Foo" renders as nothing.
+        assert_eq!(template.render(&container), "FOO, ");
+        let error = template.render_strict(&container).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "failed template filters:\n  `plural:\"x\",\"y\"` on {a} = `Foo` (token 2, offset 5)"
+        );
+        let template = Template::parse("{a | capitalize}").unwrap();
+        assert_eq!(template.render(&container), "Foo");
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        assert_eq!(
+            Template::parse("Hello {player.name").err(),
+            Some(TemplateParseError {
+                message: String::from("unclosed `{`"),
+                offset: 6,
+            })
+        );
+        assert_eq!(
+            Template::parse("Hello }").err().map(|error| error.offset),
+            Some(6)
+        );
+        assert!(Template::parse("{}").is_err());
+        assert!(Template::parse("{a | shout}").is_err());
+        assert!(Template::parse("{a | plural:note\"}").is_err());
+    }
+
+    #[test]
+    fn renders_conditionals() {
+        let container = TestContainer::new();
+        let render = |source: &str| Template::parse(source).unwrap().render(&container);
+        assert_eq!(render("{if a}yes{else}no{end}"), "yes");
+        assert_eq!(render("{if !a}yes{else}no{end}"), "no");
+        assert_eq!(render("[{if b}yes{end}]"), "[]");
+        assert_eq!(render("{if a}{if !b}nested {a}{end}{end}"), "nested foo");
+    }
+
+    #[test]
+    fn renders_loops() {
+        let mut world = World::empty();
+        world.player.inventory.add_item(Item::new(
+            String::from("rope"),
+            String::from("Rope"),
+            vec![],
+        ));
+        world.player.inventory.add_item(Item::new(
+            String::from("note"),
+            String::from("Note"),
+            vec![],
+        ));
+        world.player.inventory.add_item(Item::new(
+            String::from("note"),
+            String::from("Note"),
+            vec![],
+        ));
+        let template = Template::parse(
+            "{for item in player.inventory}{item.name} x{item.count}, {end}\
+             {if player.has_item.rope}roped{end}{if player.has_item.key}keyed{end}",
+        )
+        .unwrap();
+        assert_eq!(template.render(&world), "Note x2, Rope x1, roped");
+        let paths: Vec<&str> = template
+            .access_paths()
+            .iter()
+            .map(|path| path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "player.inventory",
+                "player.has_item.rope",
+                "player.has_item.key"
+            ]
+        );
+        assert_eq!(
+            Template::parse("{for item in player.pockets}{item.name}{end}")
+                .unwrap()
+                .render_strict(&world)
+                .unwrap_err()
+                .unresolved[0]
+                .path
+                .as_str(),
+            "player.pockets"
+        );
+    }
+
+    #[test]
+    fn reports_block_parse_errors() {
+        let error = |source: &str| Template::parse(source).err().unwrap();
+        assert_eq!(
+            error("{if a}unterminated"),
+            TemplateParseError {
+                message: String::from("`{if}` is missing its `{end}`"),
+                offset: 0,
+            }
+        );
+        assert_eq!(error("text {else}").offset, 5);
+        assert_eq!(error("text {end}").offset, 5);
+        assert_eq!(error("{if a}{else}{else}{end}").offset, 12);
+        assert!(Template::parse("{for item of list}{end}").is_err());
+        assert!(Template::parse("{for item in list}{else}{end}").is_err());
+        assert!(Template::parse("{if }{end}").is_err());
+    }
+
+    #[test]
+    fn strict_rendering_reports_unresolved_paths() {
+        let template = Template {
+            tokens: vec![
+                Token::Text(String::from("(")),
+                Token::Accessor(AccessPath::from(String::from("a"))),
+                Token::Text(String::from(", ")),
+                Token::Accessor(AccessPath::from(String::from("b"))),
+                Token::Text(String::from(")")),
+            ],
+        };
+        let container = TestContainer::new();
+        assert_eq!(template.render(&container), "(foo, )");
+        assert_eq!(
+            template.render_with_mode(&container, RenderMode::Lenient),
+            Ok(String::from("(foo, )"))
+        );
+        let error = template.render_strict(&container).unwrap_err();
+        assert_eq!(
+            error.unresolved,
+            vec![UnresolvedPath {
+                path: AccessPath::from(String::from("b")),
+                token_index: 3,
+                offset: 6,
+            }]
+        );
+        assert_eq!(
+            error.to_string(),
+            "unresolved template paths:\n  {b} (token 3, offset 6)"
+        );
+        assert_eq!(
+            Template::raw_from_str("foo").render_strict(&container),
+            Ok(String::from("foo"))
+        );
+    }
+
+    #[test]
+    fn strict_rendering_reports_failed_filters() {
+        let template = Template::parse("{a | upper}, {a | capitalize | number}").unwrap();
+        let container = TestContainer::new();
+        assert_eq!(template.render(&container), "FOO, ");
+        let error = template.render_strict(&container).unwrap_err();
+        assert!(error.unresolved.is_empty());
+        assert_eq!(
+            error.failed_filters,
+            vec![FailedFilter {
+                path: AccessPath::from(String::from("a")),
+                filter: Filter::Number(None),
+                value: String::from("Foo"),
+                token_index: 2,
+                offset: 5,
+            }]
+        );
+        assert_eq!(
+            error.to_string(),
+            "failed template filters:\n  `number` on {a} = `Foo` (token 2, offset 5)"
+        );
+    }
+
+    #[test]
+    fn parses_braces_in_quoted_filter_arguments() {
+        let template = Template::parse("{a | plural:\"}\",\"{}\"}!").unwrap();
+        assert_eq!(template.tokens.len(), 2);
+        assert_eq!(template.to_string(), "{a | plural:\"}\",\"{}\"}!");
+        assert!(Template::parse("{a | plural:\"}").is_err());
+    }
+
+    #[test]
+    fn displays_as_source() {
+        let source =
+            "{{Hi}} {player.name | upper | plural:\"a\",\"b\"}{if !vars.met}new{else}old{end}\
+                      {for item in player.inventory}{item.name | number:2}{end}";
+        let template = Template::parse(source).unwrap();
+        assert_eq!(template.to_string(), source);
+        assert_eq!(Template::raw_from_str("{raw}").to_string(), "{{raw}}");
+    }
 }
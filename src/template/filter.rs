@@ -0,0 +1,246 @@
+use std::fmt;
+
+use crate::unit::mass::Mass;
+use crate::unit::temperature::Temperature;
+use crate::unit::{UnitDisplay, UnitSystem};
+
+// A transformation applied to an accessed value before it is rendered, as in
+// `{player.name | upper}`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    Upper,
+    Lower,
+    // Uppercases the first character only.
+    Capitalize,
+    // `plural:"note","notes"` renders a count as "1 note" or "3 notes".
+    Plural(String, String),
+    // Groups thousands, optionally with a fixed number of decimal places
+    // (`number:2`).
+    Number(Option<usize>),
+    // Displays a canonical quantity ("273.15K" for temperatures, "500g" for
+    // masses) in a unit system. Defaults to metric; `units:"imperial"`.
+    Units(UnitSystem),
+}
+
+impl Filter {
+    pub fn parse(name: &str, args: &[String]) -> Result<Filter, String> {
+        let expect_args = |count: usize| {
+            if args.len() == count {
+                Ok(())
+            } else {
+                Err(format!(
+                    "filter `{}` takes {} argument(s), got {}",
+                    name,
+                    count,
+                    args.len()
+                ))
+            }
+        };
+        match name {
+            "upper" => expect_args(0).map(|_| Filter::Upper),
+            "lower" => expect_args(0).map(|_| Filter::Lower),
+            "capitalize" => expect_args(0).map(|_| Filter::Capitalize),
+            "plural" => {
+                expect_args(2)?;
+                Ok(Filter::Plural(args[0].clone(), args[1].clone()))
+            }
+            "number" => match args.len() {
+                0 => Ok(Filter::Number(None)),
+                1 => match args[0].parse::<usize>() {
+                    Ok(places) => Ok(Filter::Number(Some(places))),
+                    Err(_) => Err(format!("`{}` is not a number of decimal places", args[0])),
+                },
+                _ => expect_args(1).map(|_| Filter::Number(None)),
+            },
+            "units" => match args.len() {
+                0 => Ok(Filter::Units(UnitSystem::Metric)),
+                1 => match args[0].as_str() {
+                    "metric" => Ok(Filter::Units(UnitSystem::Metric)),
+                    "imperial" => Ok(Filter::Units(UnitSystem::Imperial)),
+                    other => Err(format!("unknown unit system `{}`", other)),
+                },
+                _ => expect_args(1).map(|_| Filter::Units(UnitSystem::Metric)),
+            },
+            _ => Err(format!("unknown filter `{}`", name)),
+        }
+    }
+
+    // Returns None when the value cannot be filtered, e.g. pluralizing
+    // something that is not a number.
+    pub fn apply(&self, value: &str) -> Option<String> {
+        match self {
+            Filter::Upper => Some(value.to_uppercase()),
+            Filter::Lower => Some(value.to_lowercase()),
+            Filter::Capitalize => {
+                let mut chars = value.chars();
+                Some(match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                })
+            }
+            Filter::Plural(singular, plural) => {
+                let count = value.trim().parse::<i64>().ok()?;
+                let word = if count == 1 { singular } else { plural };
+                Some(format!("{} {}", count, word))
+            }
+            Filter::Number(places) => format_number(value.trim(), *places),
+            Filter::Units(system) => format_units(value.trim(), *system),
+        }
+    }
+}
+
+// Writes the filter as it appears in a template, e.g. `plural:"note","notes"`.
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Filter::Upper => write!(f, "upper"),
+            Filter::Lower => write!(f, "lower"),
+            Filter::Capitalize => write!(f, "capitalize"),
+            Filter::Plural(singular, plural) => {
+                write!(f, "plural:\"{}\",\"{}\"", singular, plural)
+            }
+            Filter::Number(None) => write!(f, "number"),
+            Filter::Number(Some(places)) => write!(f, "number:{}", places),
+            Filter::Units(UnitSystem::Metric) => write!(f, "units"),
+            Filter::Units(UnitSystem::Imperial) => write!(f, "units:\"imperial\""),
+        }
+    }
+}
+
+fn format_number(value: &str, places: Option<usize>) -> Option<String> {
+    let number = value.parse::<f64>().ok()?;
+    if !number.is_finite() {
+        return None;
+    }
+    let formatted = match places {
+        Some(places) => format!("{:.*}", places, number),
+        // Exponents (`1e6`) are written out in full; otherwise the digits
+        // are kept as given.
+        None if value.contains(['e', 'E']) => number.to_string(),
+        None => value.trim_start_matches('+').to_string(),
+    };
+    let (sign, unsigned) = match formatted.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", formatted.as_str()),
+    };
+    let (integer, fraction) = match unsigned.find('.') {
+        Some(point) => unsigned.split_at(point),
+        None => (unsigned, ""),
+    };
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    Some(format!("{}{}{}", sign, grouped, fraction))
+}
+
+fn format_units(value: &str, system: UnitSystem) -> Option<String> {
+    if let Some(kelvin) = value.strip_suffix('K') {
+        let kelvin = kelvin.trim().parse::<f64>().ok()?;
+        if !kelvin.is_finite() || kelvin < 0.0 {
+            return None;
+        }
+        return Some(Temperature::from_kelvin(kelvin).display_with_units(system));
+    }
+    if let Some(grams) = value.strip_suffix('g') {
+        let grams = grams.trim().parse::<i64>().ok()?;
+        return Some(Mass::from_grams(grams).display_with_units(system));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_filters() {
+        assert_eq!(Filter::parse("upper", &[]), Ok(Filter::Upper));
+        assert_eq!(
+            Filter::parse("plural", &args(&["note", "notes"])),
+            Ok(Filter::Plural(String::from("note"), String::from("notes")))
+        );
+        assert_eq!(
+            Filter::parse("number", &args(&["2"])),
+            Ok(Filter::Number(Some(2)))
+        );
+        assert_eq!(
+            Filter::parse("units", &args(&["imperial"])),
+            Ok(Filter::Units(UnitSystem::Imperial))
+        );
+        assert!(Filter::parse("upper", &args(&["x"])).is_err());
+        assert!(Filter::parse("plural", &args(&["note"])).is_err());
+        assert!(Filter::parse("shout", &[]).is_err());
+    }
+
+    #[test]
+    fn changes_case() {
+        assert_eq!(Filter::Upper.apply("Stan"), Some(String::from("STAN")));
+        assert_eq!(Filter::Lower.apply("Stan"), Some(String::from("stan")));
+        assert_eq!(
+            Filter::Capitalize.apply("stan the ranger"),
+            Some(String::from("Stan the ranger"))
+        );
+        assert_eq!(Filter::Capitalize.apply(""), Some(String::new()));
+    }
+
+    #[test]
+    fn pluralizes_counts() {
+        let filter = Filter::Plural(String::from("note"), String::from("notes"));
+        assert_eq!(filter.apply("1"), Some(String::from("1 note")));
+        assert_eq!(filter.apply("0"), Some(String::from("0 notes")));
+        assert_eq!(filter.apply("3"), Some(String::from("3 notes")));
+        assert_eq!(filter.apply("many"), None);
+    }
+
+    #[test]
+    fn formats_numbers() {
+        assert_eq!(Filter::Number(None).apply("12"), Some(String::from("12")));
+        assert_eq!(
+            Filter::Number(None).apply("1234567"),
+            Some(String::from("1,234,567"))
+        );
+        assert_eq!(
+            Filter::Number(None).apply("-1234.5"),
+            Some(String::from("-1,234.5"))
+        );
+        assert_eq!(
+            Filter::Number(Some(2)).apply("1234.5"),
+            Some(String::from("1,234.50"))
+        );
+        assert_eq!(Filter::Number(None).apply("lots"), None);
+        assert_eq!(
+            Filter::Number(None).apply("1.5e6"),
+            Some(String::from("1,500,000"))
+        );
+        assert_eq!(
+            Filter::Number(None).apply("-2E-3"),
+            Some(String::from("-0.002"))
+        );
+    }
+
+    #[test]
+    fn displays_units() {
+        assert_eq!(
+            Filter::Units(UnitSystem::Metric).apply("273.15K"),
+            Some(String::from("0.0 °C"))
+        );
+        assert_eq!(
+            Filter::Units(UnitSystem::Imperial).apply("273.15K"),
+            Some(String::from("32.0 °F"))
+        );
+        assert_eq!(
+            Filter::Units(UnitSystem::Metric).apply("500g"),
+            Some(String::from("500g"))
+        );
+        assert_eq!(Filter::Units(UnitSystem::Metric).apply("-5K"), None);
+        assert_eq!(Filter::Units(UnitSystem::Metric).apply("warm"), None);
+    }
+}
@@ -6,6 +6,7 @@ pub mod temperature;
 // Units easily represented with an integer are represented as one.
 pub const UNIT_PRECISION: f64 = 0.001;
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum UnitSystem {
     Metric,
     Imperial,
@@ -7,11 +7,13 @@ pub struct Mass {
     grams: i64,
 }
 
+#[allow(clippy::wrong_self_convention)]
 impl Mass {
     pub fn from_grams(grams: i64) -> Mass {
         Mass { grams }
     }
 
+    #[allow(dead_code)]
     pub fn to_grams(&self) -> i64 {
         self.grams
     }
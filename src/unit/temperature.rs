@@ -13,6 +13,7 @@ pub struct Temperature {
 // If close enough to absolute zero to constitute equality, they will be left
 // alone.
 // Otherwise, the program will crash.
+#[allow(clippy::wrong_self_convention)]
 impl Temperature {
     pub const fn absolute_zero() -> Temperature {
         Temperature{kelvin: 0.0}
@@ -24,12 +25,14 @@ impl Temperature {
         t
     }
 
+    #[allow(dead_code)]
     pub fn from_celsius(celsius: f64) -> Temperature {
         let t = Temperature{kelvin: celsius + 273.15};
         t.check_validity();
         t
     }
 
+    #[allow(dead_code)]
     pub fn from_farenheit(farenheit: f64) -> Temperature {
         Temperature::from_celsius((farenheit - 32.0) * 5.0 / 9.0)
     }
@@ -198,6 +201,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn converts_to_kelvin() {
         assert!((Temperature::from_kelvin(3.1415).to_kelvin()
                  - 3.1415).abs() < UNIT_PRECISION);
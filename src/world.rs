@@ -1,28 +1,114 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
 use crate::accessible::{Accessible, Accessor};
 use crate::character::Character;
 use crate::stat::StatBlock;
 
+#[derive(Clone)]
 pub struct World {
     pub player: Character,
-    accessor: Accessor<World>,
+    pub vars: Variables,
+    pub markers: Markers,
+}
+
+// Values the story stores for itself, such as answers to its questions. Any
+// name can be assigned.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Variables {
+    values: BTreeMap<String, String>,
+}
+
+impl Accessible for Variables {
+    fn lookup_local(&self, property: &str) -> Option<String> {
+        self.values.get(property).cloned()
+    }
+    fn property_names(&self) -> Vec<&str> {
+        self.values.keys().map(String::as_str).collect()
+    }
+    fn set_local(&mut self, property: &str, value: &str) -> bool {
+        self.values.insert(property.to_string(), value.to_string());
+        true
+    }
+}
+
+// Flags the story sets with `Mark` as things happen, e.g. meeting someone.
+// Set markers look up as "true".
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Markers {
+    set: BTreeSet<String>,
+}
+
+impl Markers {
+    pub fn set(&mut self, marker: &str) {
+        self.set.insert(marker.to_string());
+    }
+
+    pub fn is_set(&self, marker: &str) -> bool {
+        self.set.contains(marker)
+    }
+
+    // For saves made before the story renamed markers, old names to new
+    // ones. All the old names go before any new one is set, so that markers
+    // can swap names and one rename does not feed into another.
+    pub fn rename(&mut self, renames: &HashMap<String, String>) {
+        let renamed: Vec<&String> = renames
+            .iter()
+            .filter(|(from, _)| self.set.remove(*from))
+            .map(|(_, to)| to)
+            .collect();
+        for to in renamed {
+            self.set(to);
+        }
+    }
+}
+
+impl Accessible for Markers {
+    fn lookup_local(&self, property: &str) -> Option<String> {
+        if self.is_set(property) {
+            Some(String::from("true"))
+        } else {
+            None
+        }
+    }
+    fn property_names(&self) -> Vec<&str> {
+        self.set.iter().map(String::as_str).collect()
+    }
 }
 
+static WORLD_ACCESSOR: Accessor<World> = Accessor::new(&[]);
+
 impl World {
     pub fn empty() -> World {
         World {
             player: Character::new(String::from("Player"), StatBlock::new()),
-            accessor: Accessor::new(),
+            vars: Variables::default(),
+            markers: Markers::default(),
         }
     }
 }
 
 impl Accessible for World {
     fn lookup_local(&self, property: &str) -> Option<String> {
-        self.accessor.lookup(property, self)
+        WORLD_ACCESSOR.lookup(property, self)
+    }
+    fn property_names(&self) -> Vec<&str> {
+        WORLD_ACCESSOR.property_names()
+    }
+    fn child_names(&self) -> Vec<&str> {
+        vec!["player", "vars", "markers"]
     }
-    fn get_child(&self, child: &str) -> Option<&Accessible> {
+    fn get_child(&self, child: &str) -> Option<&dyn Accessible> {
         match child {
             "player" => Some(&self.player),
+            "vars" => Some(&self.vars),
+            "markers" => Some(&self.markers),
+            _ => None,
+        }
+    }
+    fn get_child_mut(&mut self, child: &str) -> Option<&mut dyn Accessible> {
+        match child {
+            "player" => Some(&mut self.player),
+            "vars" => Some(&mut self.vars),
             _ => None,
         }
     }
@@ -41,4 +127,72 @@ mod tests {
             Some(String::from("Player"))
         );
     }
+
+    #[test]
+    fn lists_property_paths() {
+        let world = World::empty();
+        assert_eq!(world.property_paths(), vec!["player.name"]);
+        assert!(world.has_path(AccessPath::from(String::from("player.name")).view()));
+        assert!(!world.has_path(AccessPath::from(String::from("player.nmae")).view()));
+    }
+
+    #[test]
+    fn assigns_through_paths() {
+        let mut world = World::empty();
+        assert!(world.assign(AccessPath::from(String::from("player.name")).view(), "Ada"));
+        assert!(world.assign(AccessPath::from(String::from("vars.riddle")).view(), "echo"));
+        assert!(!world.assign(AccessPath::from(String::from("player.nmae")).view(), "Ada"));
+        assert!(!world.assign(AccessPath::from(String::from("vars")).view(), "echo"));
+        assert_eq!(world.player.name, "Ada");
+        assert_eq!(
+            world.lookup(AccessPath::from(String::from("vars.riddle")).view()),
+            Some(String::from("echo"))
+        );
+        assert_eq!(world.property_paths(), vec!["player.name", "vars.riddle"]);
+    }
+
+    #[test]
+    fn looks_up_markers() {
+        let mut world = World::empty();
+        world.markers.set("MetStan");
+        assert!(world.markers.is_set("MetStan"));
+        assert_eq!(
+            world.lookup(AccessPath::from(String::from("markers.MetStan")).view()),
+            Some(String::from("true"))
+        );
+        assert_eq!(
+            world.lookup(AccessPath::from(String::from("markers.KilledStan")).view()),
+            None
+        );
+    }
+
+    #[test]
+    fn renames_markers_all_at_once() {
+        let mut markers = Markers::default();
+        markers.set("A");
+        markers.set("B");
+        markers.set("Kept");
+        let renames = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+                .collect()
+        };
+        markers.rename(&renames(&[("A", "B"), ("B", "A")]));
+        assert_eq!(markers.property_names(), vec!["A", "B", "Kept"]);
+        markers.rename(&renames(&[("A", "B"), ("B", "C"), ("Unset", "D")]));
+        assert_eq!(markers.property_names(), vec!["B", "C", "Kept"]);
+    }
+
+    #[test]
+    fn can_be_cloned_and_sent_across_threads() {
+        let world = World::empty();
+        let cloned = world.clone();
+        let name = std::thread::spawn(move || {
+            cloned.lookup(AccessPath::from(String::from("player.name")).view())
+        })
+        .join()
+        .unwrap();
+        assert_eq!(name, Some(String::from("Player")));
+    }
 }